@@ -0,0 +1,164 @@
+//! A cache of where each package in a workspace's dependency graph lives, so repeated runs
+//! can skip `cargo metadata`.
+//!
+//! Caches live in `$CARGO_HOME/cargo-open/cache`, one per manifest and set of metadata options.
+//! Each records a hash of `Cargo.lock` and every local manifest, and is only used while they're
+//! unchanged, or still missing.
+
+use crate::{
+    hash::{self, StableHasher},
+    lockfile::cargo_home,
+    spec::PackageSpec,
+    MetadataArgs,
+};
+use cargo_metadata::{semver::Version, Metadata};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Bumped whenever the format changes, so caches from older versions are ignored.
+const CACHE_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+pub struct Cache {
+    version: u32,
+    fingerprints: Vec<Fingerprint>,
+    packages: Vec<CachedPackage>,
+}
+
+/// The hash of a file the dependency graph was resolved from.
+#[derive(Serialize, Deserialize)]
+struct Fingerprint {
+    path: PathBuf,
+    /// `None` if the file didn't exist, as creating one, like `Cargo.lock`, changes resolution too.
+    hash: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedPackage {
+    name: String,
+    version: Version,
+    /// The package's source, or the url part of its id for path packages.
+    source: String,
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Loads the cache for the current manifest and options, if there is one and it's still valid.
+    pub fn load(args: &MetadataArgs) -> Option<Self> {
+        let cache: Cache = serde_json::from_slice(&fs::read(cache_path(args)?).ok()?).ok()?;
+
+        let is_valid = cache.version == CACHE_VERSION
+            && cache
+                .fingerprints
+                .iter()
+                .all(|fingerprint| hash_file(&fingerprint.path) == fingerprint.hash);
+
+        is_valid.then_some(cache)
+    }
+
+    /// Finds the directory of the one cached package matching `spec` by exact name.
+    pub fn find_package_dir(&self, spec: &PackageSpec) -> Option<PathBuf> {
+        let mut matches = self.packages.iter().filter(|package| {
+            spec.matches_locked(&package.name, &package.version, &package.source)
+        });
+
+        let package = matches.next()?;
+        if matches.next().is_some() {
+            return None;
+        }
+
+        package.dir.is_dir().then(|| package.dir.clone())
+    }
+}
+
+/// Writes the cache for freshly resolved metadata. Failing to is harmless, so errors are ignored.
+pub fn store(args: &MetadataArgs, metadata: &Metadata) {
+    let Some(path) = cache_path(args) else {
+        return;
+    };
+
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let mut files = vec![
+        workspace_root.join("Cargo.toml"),
+        workspace_root.join("Cargo.lock"),
+    ];
+    files.extend(
+        metadata
+            .packages
+            .iter()
+            .filter(|package| package.source.is_none())
+            .map(|package| package.manifest_path.clone().into_std_path_buf()),
+    );
+    files.sort();
+    files.dedup();
+
+    let fingerprints = files
+        .into_iter()
+        .map(|path| Fingerprint {
+            hash: hash_file(&path),
+            path,
+        })
+        .collect();
+
+    let packages = metadata
+        .packages
+        .iter()
+        .filter_map(|package| {
+            let source = match &package.source {
+                Some(source) => source.repr.clone(),
+                None => package.id.repr.split('#').next()?.to_string(),
+            };
+            Some(CachedPackage {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                source,
+                dir: package.manifest_path.parent()?.to_path_buf().into_std_path_buf(),
+            })
+        })
+        .collect();
+
+    let cache = Cache {
+        version: CACHE_VERSION,
+        fingerprints,
+        packages,
+    };
+
+    if let Ok(json) = serde_json::to_vec(&cache) {
+        let _ = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, json));
+    }
+}
+
+/// Caches are named after a hash of the manifest cargo would use and the options that affect resolution.
+fn cache_path(args: &MetadataArgs) -> Option<PathBuf> {
+    let manifest = match &args.manifest_path {
+        Some(manifest_path) => fs::canonicalize(manifest_path).ok()?,
+        None => std::env::current_dir()
+            .ok()?
+            .ancestors()
+            .map(|dir| dir.join("Cargo.toml"))
+            .find(|manifest| manifest.is_file())?,
+    };
+
+    let mut hasher = StableHasher::default();
+    hasher.write_field(manifest.as_os_str().as_encoded_bytes());
+    for list in [&args.features, &args.filter_platform] {
+        hasher.write(&(list.len() as u64).to_le_bytes());
+        for value in list {
+            hasher.write_field(value.as_bytes());
+        }
+    }
+    hasher.write(&[args.all_features as u8, args.no_default_features as u8]);
+
+    let name = format!("{:016x}.json", hasher.finish());
+    Some(cargo_home()?.join("cargo-open").join("cache").join(name))
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    fs::read(path).ok().map(|contents| hash::hash_bytes(&contents))
+}
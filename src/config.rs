@@ -0,0 +1,435 @@
+//! Settings layered from config files, the environment and the command line.
+//!
+//! Later layers override earlier ones, key by key:
+//! `$XDG_CONFIG_HOME/cargo-open/config.toml`, the `[open]` tables of cargo's own config files
+//! (`$CARGO_HOME/config.toml`, then each `.cargo/config.toml` from the filesystem root down to the
+//! current directory, as cargo discovers them), environment variables, then command line options.
+
+use crate::{item::split_item_path, lockfile::cargo_home, output, Format};
+use clap::{error::ErrorKind, Error, ValueEnum};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// Where a setting's value came from.
+#[derive(Clone)]
+pub enum Source {
+    File(PathBuf),
+    Env(&'static str),
+    Cli(&'static str),
+    /// The editor configured for git, with `core.editor`.
+    Git,
+    /// A program found on `PATH` when nothing was configured.
+    Path(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(var) => write!(f, "{} environment variable", var),
+            Source::Cli(flag) => write!(f, "{} option", flag),
+            Source::Git => write!(f, "git config core.editor"),
+            Source::Path(path) => write!(f, "{} found on PATH", path.display()),
+        }
+    }
+}
+
+/// A value along with where it was set.
+#[derive(Clone)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+/// The effective configuration, with every layer applied.
+#[derive(Default)]
+pub struct Config {
+    /// The editor command, split into words like a shell would.
+    pub editor: Option<Setting<String>>,
+    /// How to pass a file and position to the editor, e.g. `{editor} --goto {file}:{line}:{column}`.
+    pub line_template: Option<Setting<String>>,
+    /// Whether to wait for the editor, overriding the default for its kind.
+    pub wait: Option<Setting<bool>>,
+    pub format: Option<Setting<Format>>,
+    /// Names that stand for other crate names or specs, like `tokio1 = "tokio@1"`.
+    pub aliases: BTreeMap<String, Setting<String>>,
+}
+
+/// The keys understood in a config file, or in an `[open]` table of cargo's config.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+struct ConfigFile {
+    editor: Option<String>,
+    line_template: Option<String>,
+    wait: Option<bool>,
+    format: Option<String>,
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+}
+
+/// Cargo's config files, of which only the `[open]` table is ours.
+#[derive(Deserialize)]
+struct CargoConfigFile {
+    #[serde(default)]
+    open: ConfigFile,
+}
+
+impl Config {
+    /// Loads the config files and environment. Command line options are applied by the caller.
+    pub fn load() -> Result<Self, Error> {
+        let mut config = Config::default();
+
+        if let Some(path) = user_config_path().filter(|path| path.is_file()) {
+            let file: ConfigFile = read_toml(&path)?;
+            config.merge(file, &path)?;
+        }
+
+        for path in cargo_config_paths() {
+            let file: CargoConfigFile = read_toml(&path)?;
+            config.merge(file.open, &path)?;
+        }
+
+        config.merge_env(|var| std::env::var(var).ok())?;
+        Ok(config)
+    }
+
+    fn merge(&mut self, file: ConfigFile, path: &Path) -> Result<(), Error> {
+        let source = Source::File(path.to_path_buf());
+        let setting = |value| Setting {
+            value,
+            source: source.clone(),
+        };
+
+        if let Some(editor) = file.editor {
+            self.editor = Some(setting(editor));
+        }
+        if let Some(line_template) = file.line_template {
+            self.line_template = Some(setting(line_template));
+        }
+        if let Some(wait) = file.wait {
+            self.wait = Some(Setting {
+                value: wait,
+                source: source.clone(),
+            });
+        }
+        if let Some(format) = file.format {
+            self.format = Some(Setting {
+                value: parse_format(&format, &source)?,
+                source: source.clone(),
+            });
+        }
+        for (name, spec) in file.aliases {
+            self.aliases.insert(name, setting(spec));
+        }
+
+        Ok(())
+    }
+
+    /// `CARGO_OPEN_*` variables mirror the config keys, as cargo's own `CARGO_*` variables do.
+    /// `CARGO_EDITOR` is also honoured, while the general `VISUAL` and `EDITOR` variables, which
+    /// are set for every program, only apply when no config file sets an editor. As with git, an
+    /// editor variable that's empty counts as unset.
+    fn merge_env(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<(), Error> {
+        let env = |var: &'static str| {
+            let value = lookup(var)?;
+            Some(Setting {
+                value,
+                source: Source::Env(var),
+            })
+        };
+        let editor_env =
+            |var: &'static str| env(var).filter(|setting| !setting.value.trim().is_empty());
+
+        if self.editor.is_none() {
+            self.editor = editor_env("VISUAL").or_else(|| editor_env("EDITOR"));
+        }
+        let cargo_editor = editor_env("CARGO_OPEN_EDITOR").or_else(|| editor_env("CARGO_EDITOR"));
+        if let Some(editor) = cargo_editor {
+            self.editor = Some(editor);
+        }
+        if let Some(line_template) = env("CARGO_OPEN_LINE_TEMPLATE") {
+            self.line_template = Some(line_template);
+        }
+        if let Some(wait) = env("CARGO_OPEN_WAIT") {
+            let value = match wait.value.as_str() {
+                "true" => true,
+                "false" => false,
+                _ => {
+                    return Err(Error::raw(
+                        ErrorKind::InvalidValue,
+                        format!(
+                            "Invalid value {:?} for {}, expected true or false",
+                            wait.value, wait.source
+                        ),
+                    ))
+                }
+            };
+            self.wait = Some(Setting {
+                value,
+                source: wait.source,
+            });
+        }
+        if let Some(format) = env("CARGO_OPEN_FORMAT") {
+            self.format = Some(Setting {
+                value: parse_format(&format.value, &format.source)?,
+                source: format.source,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Replaces an alias at the start of a crate name or item path with what it stands for.
+    pub fn expand_alias(&self, package_name: &str) -> String {
+        let (crate_name, item_path) = split_item_path(package_name);
+
+        match (self.aliases.get(crate_name), item_path) {
+            (Some(alias), Some(item_path)) => format!("{}::{}", alias.value, item_path),
+            (Some(alias), None) => alias.value.clone(),
+            (None, _) => package_name.to_string(),
+        }
+    }
+
+    /// Prints every setting in config file syntax, commented with where it came from.
+    pub fn show(&self) -> Result<(), Error> {
+        let mut out = String::new();
+        let mut line = |key: &str, value: Option<(String, &Source)>| match value {
+            Some((value, source)) => out.push_str(&format!("{} = {}  # {}\n", key, value, source)),
+            None => out.push_str(&format!("# {} is not set\n", key)),
+        };
+
+        let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+        line(
+            "editor",
+            self.editor
+                .as_ref()
+                .map(|setting| (quote(&setting.value), &setting.source)),
+        );
+        line(
+            "line-template",
+            self.line_template
+                .as_ref()
+                .map(|setting| (quote(&setting.value), &setting.source)),
+        );
+        line(
+            "wait",
+            self.wait
+                .as_ref()
+                .map(|setting| (setting.value.to_string(), &setting.source)),
+        );
+        line(
+            "format",
+            self.format.as_ref().map(|setting| {
+                let name = setting
+                    .value
+                    .to_possible_value()
+                    .map(|value| value.get_name().to_string());
+                (quote(&name.unwrap_or_default()), &setting.source)
+            }),
+        );
+
+        if self.aliases.is_empty() {
+            line("aliases", None);
+        }
+        for (name, alias) in &self.aliases {
+            line(
+                &format!("aliases.{}", quote_key(name)),
+                Some((quote(&alias.value), &alias.source)),
+            );
+        }
+
+        output::write_stdout(&[out.as_bytes()])
+    }
+}
+
+/// The per-user config file, `$XDG_CONFIG_HOME/cargo-open/config.toml`,
+/// defaulting to `~/.config/cargo-open/config.toml`.
+fn user_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::home_dir().map(|home| home.join(".config")))?;
+
+    Some(config_home.join("cargo-open").join("config.toml"))
+}
+
+/// Cargo's config files in increasing order of precedence: `$CARGO_HOME/config.toml`,
+/// then `.cargo/config.toml` in each directory from the root down to the current one.
+fn cargo_config_paths() -> Vec<PathBuf> {
+    let find = |dir: &Path| {
+        ["config.toml", "config"]
+            .into_iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    };
+
+    let mut paths: Vec<PathBuf> = std::env::current_dir()
+        .map(|dir| {
+            dir.ancestors()
+                .filter_map(|dir| find(&dir.join(".cargo")))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if let Some(path) = cargo_home().and_then(|cargo_home| find(&cargo_home)) {
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+
+    paths.reverse();
+    paths
+}
+
+fn read_toml<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, Error> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        Error::raw(
+            ErrorKind::Io,
+            format!("Cannot read {}: {}", path.display(), e),
+        )
+    })?;
+
+    toml::from_str(&contents).map_err(|e| {
+        Error::raw(
+            ErrorKind::InvalidValue,
+            format!("Cannot parse {}: {}", path.display(), e),
+        )
+    })
+}
+
+fn parse_format(value: &str, source: &Source) -> Result<Format, Error> {
+    Format::from_str(value, false).map_err(|_| {
+        Error::raw(
+            ErrorKind::InvalidValue,
+            format!("Invalid format {:?} in {}", value, source),
+        )
+    })
+}
+
+/// Quotes a table key if it isn't a valid bare key.
+fn quote_key(key: &str) -> String {
+    let is_bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if is_bare {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge_file(config: &mut Config, path: &str, contents: &str) {
+        let file: ConfigFile = toml::from_str(contents).unwrap();
+        config.merge(file, Path::new(path)).unwrap();
+    }
+
+    fn merge_env(config: &mut Config, vars: &[(&str, &str)]) -> Result<(), Error> {
+        config.merge_env(|var| {
+            vars.iter()
+                .find(|(name, _)| *name == var)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    /// The value of a setting and where it came from.
+    fn describe<T: Clone>(setting: &Option<Setting<T>>) -> Option<(T, String)> {
+        setting
+            .as_ref()
+            .map(|setting| (setting.value.clone(), setting.source.to_string()))
+    }
+
+    #[test]
+    fn later_files_override_earlier_ones_key_by_key() {
+        let mut config = Config::default();
+        merge_file(
+            &mut config,
+            "user.toml",
+            "editor = \"vim\"\nwait = false\n[aliases]\ntokio1 = \"tokio@1\"\nsyn1 = \"syn@1\"\n",
+        );
+        merge_file(
+            &mut config,
+            "cargo.toml",
+            "editor = \"code --wait\"\nformat = \"json\"\n[aliases]\nsyn1 = \"syn@1.0.109\"\n",
+        );
+
+        assert_eq!(describe(&config.editor), Some(("code --wait".into(), "cargo.toml".into())));
+        assert_eq!(describe(&config.wait), Some((false, "user.toml".into())));
+        assert!(matches!(config.format, Some(Setting { value: Format::Json, .. })));
+        assert_eq!(config.expand_alias("tokio1::spawn"), "tokio@1::spawn");
+        assert_eq!(config.expand_alias("syn1"), "syn@1.0.109");
+        assert_eq!(config.expand_alias("serde"), "serde");
+    }
+
+    #[test]
+    fn general_editor_variables_only_apply_without_a_configured_editor() {
+        let vars = [("VISUAL", "gvim"), ("EDITOR", "vi")];
+
+        let mut config = Config::default();
+        merge_env(&mut config, &vars).unwrap();
+        let visual = "VISUAL environment variable";
+        assert_eq!(describe(&config.editor), Some(("gvim".into(), visual.into())));
+
+        let mut config = Config::default();
+        merge_env(&mut config, &[("EDITOR", "vi")]).unwrap();
+        assert_eq!(describe(&config.editor).unwrap().0, "vi");
+
+        let mut config = Config::default();
+        merge_file(&mut config, "user.toml", "editor = \"hx\"\n");
+        merge_env(&mut config, &vars).unwrap();
+        assert_eq!(describe(&config.editor), Some(("hx".into(), "user.toml".into())));
+    }
+
+    #[test]
+    fn cargo_editor_variables_override_config_files() {
+        let mut config = Config::default();
+        merge_file(&mut config, "user.toml", "editor = \"hx\"\n");
+        merge_env(&mut config, &[("CARGO_EDITOR", "nano"), ("VISUAL", "gvim")]).unwrap();
+        assert_eq!(describe(&config.editor).unwrap().0, "nano");
+
+        let vars = [("CARGO_OPEN_EDITOR", "zed"), ("CARGO_EDITOR", "nano")];
+        merge_env(&mut config, &vars).unwrap();
+        let source = "CARGO_OPEN_EDITOR environment variable";
+        assert_eq!(describe(&config.editor), Some(("zed".into(), source.into())));
+    }
+
+    #[test]
+    fn empty_editor_variables_are_unset() {
+        let mut config = Config::default();
+        let vars = [("CARGO_EDITOR", ""), ("VISUAL", " "), ("EDITOR", "vi")];
+        merge_env(&mut config, &vars).unwrap();
+        assert_eq!(describe(&config.editor).unwrap().0, "vi");
+
+        let mut config = Config::default();
+        merge_file(&mut config, "user.toml", "editor = \"hx\"\n");
+        merge_env(&mut config, &[("CARGO_OPEN_EDITOR", "")]).unwrap();
+        assert_eq!(describe(&config.editor).unwrap().0, "hx");
+    }
+
+    #[test]
+    fn parses_other_variables() {
+        let mut config = Config::default();
+        merge_file(&mut config, "user.toml", "wait = false\nformat = \"json\"\n");
+        let vars = [
+            ("CARGO_OPEN_WAIT", "true"),
+            ("CARGO_OPEN_FORMAT", "human"),
+            ("CARGO_OPEN_LINE_TEMPLATE", "{editor} {file}:{line}"),
+        ];
+        merge_env(&mut config, &vars).unwrap();
+        assert!(describe(&config.wait).unwrap().0);
+        assert!(matches!(config.format, Some(Setting { value: Format::Human, .. })));
+        assert_eq!(describe(&config.line_template).unwrap().0, "{editor} {file}:{line}");
+
+        assert!(merge_env(&mut config, &[("CARGO_OPEN_WAIT", "yes")]).is_err());
+        assert!(merge_env(&mut config, &[("CARGO_OPEN_FORMAT", "xml")]).is_err());
+    }
+}
@@ -0,0 +1,574 @@
+//! Resolving, launching and waiting on the user's editor.
+
+use crate::config::{Config, Setting, Source};
+use clap::{error::ErrorKind, Error};
+use std::{
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitCode, ExitStatus},
+};
+
+/// A file or directory to open, at a position if it's a file.
+pub struct Target {
+    pub path: PathBuf,
+    pub position: Option<Position>,
+}
+
+/// A 1-based line and column to open a file at.
+#[derive(Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// How to open a file at a position in a particular editor, and whether it runs in its own window.
+struct Adapter {
+    /// Binary names the editor is known by.
+    names: &'static [&'static str],
+    /// Arguments following the editor command, with `{file}`, `{line}` and `{column}` placeholders.
+    template: &'static [&'static str],
+    gui: bool,
+}
+
+const ADAPTERS: &[Adapter] = &[
+    Adapter {
+        names: &["vi", "vim", "nvim", "view", "ex", "joe", "jed"],
+        template: &["+{line}", "{file}"],
+        gui: false,
+    },
+    Adapter {
+        names: &["gvim", "mvim"],
+        template: &["+{line}", "{file}"],
+        gui: true,
+    },
+    Adapter {
+        names: &["nano", "pico"],
+        template: &["+{line},{column}", "{file}"],
+        gui: false,
+    },
+    Adapter {
+        names: &["emacs", "emacsclient", "kak", "micro"],
+        template: &["+{line}:{column}", "{file}"],
+        gui: false,
+    },
+    Adapter {
+        names: &["hx", "helix"],
+        template: &["{file}:{line}:{column}"],
+        gui: false,
+    },
+    Adapter {
+        names: &["code", "code-insiders", "codium", "cursor"],
+        template: &["-g", "{file}:{line}:{column}"],
+        gui: true,
+    },
+    Adapter {
+        names: &["subl", "sublime_text", "zed", "zeditor", "atom"],
+        template: &["{file}:{line}:{column}"],
+        gui: true,
+    },
+    Adapter {
+        names: &["idea", "idea64", "clion", "rustrover", "webstorm", "pycharm"],
+        template: &["--line", "{line}", "{file}"],
+        gui: true,
+    },
+    Adapter {
+        names: &["mate"],
+        template: &["-l", "{line}:{column}", "{file}"],
+        gui: true,
+    },
+    Adapter {
+        names: &["kate"],
+        template: &["-l", "{line}", "-c", "{column}", "{file}"],
+        gui: true,
+    },
+    Adapter {
+        names: &["gedit", "gnome-text-editor"],
+        template: &["+{line}:{column}", "{file}"],
+        gui: true,
+    },
+    Adapter {
+        names: &["xdg-open", "open"],
+        template: &["{file}"],
+        gui: true,
+    },
+];
+
+/// The `+LINE` convention understood by most terminal editors, for editors not in [`ADAPTERS`].
+const DEFAULT_TEMPLATE: &[&str] = &["+{line}", "{file}"];
+
+/// An editor command line, split into the program and any leading arguments
+/// that precede the path being opened.
+pub struct Editor {
+    program: PathBuf,
+    args: Vec<String>,
+    /// A user-supplied line template, already split into words.
+    line_template: Option<Vec<String>>,
+}
+
+impl Editor {
+    fn adapter(&self) -> Option<&'static Adapter> {
+        let name = self
+            .program
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+
+        ADAPTERS.iter().find(|adapter| adapter.names.contains(&name))
+    }
+
+    /// Terminal editors need the TTY to themselves, so they are waited on unless
+    /// the user asks otherwise. GUI editors are only waited on when told to via `--wait`.
+    pub fn waits_by_default(&self) -> bool {
+        !self.adapter().is_some_and(|adapter| adapter.gui)
+            || self.args.iter().any(|arg| arg == "--wait" || arg == "-w")
+    }
+
+    /// Builds a single command opening all of `targets`. Adapter templates are repeated for
+    /// each target with a position, as is everything following the program in a user template.
+    fn command(&self, targets: &[Target]) -> Command {
+        let user_template = self.line_template.as_ref().filter(|_| {
+            targets.iter().any(|target| target.position.is_some())
+        });
+
+        if let Some(template) = user_template {
+            let (program, words) = match template.iter().position(|word| word == "{editor}") {
+                Some(index) => {
+                    let mut program = template[..index].to_vec();
+                    program.push(self.program.to_string_lossy().into_owned());
+                    program.extend(self.args.iter().cloned());
+                    (program, &template[index + 1..])
+                }
+                None => (template[..1].to_vec(), &template[1..]),
+            };
+
+            let mut cmd = Command::new(&program[0]);
+            cmd.args(&program[1..]);
+            for target in targets {
+                match target.position {
+                    Some(position) => {
+                        cmd.args(words.iter().map(|word| expand(word, &target.path, position)))
+                    }
+                    None => cmd.arg(&target.path),
+                };
+            }
+            return cmd;
+        }
+
+        let template = self
+            .adapter()
+            .map_or(DEFAULT_TEMPLATE, |adapter| adapter.template);
+
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        for target in targets {
+            match target.position {
+                Some(position) => {
+                    cmd.args(template.iter().map(|word| expand(word, &target.path, position)))
+                }
+                None => cmd.arg(&target.path),
+            };
+        }
+        cmd
+    }
+}
+
+/// Fills in the `{file}`, `{line}` and `{column}` placeholders of a template word.
+fn expand(word: &str, path: &Path, position: Position) -> String {
+    word.replace("{file}", &path.to_string_lossy())
+        .replace("{line}", &position.line.to_string())
+        .replace("{column}", &position.column.to_string())
+}
+
+/// Editors tried in order when nothing is configured, preferring ones that open directories well.
+const PROBED_EDITORS: &[&str] = &["code", "nvim", "vim", "hx", "nano"];
+
+/// Resolves the configured editor, falling back to [`detect_editor`], and reports
+/// where it came from when `verbose` is set.
+pub fn get_editor(config: &Config, verbose: bool) -> Result<Editor, Error> {
+    let setting = config
+        .editor
+        .clone()
+        .or_else(detect_editor)
+        .ok_or_else(|| {
+            Error::raw(
+                ErrorKind::Io,
+                "Cannot resolve editor, set CARGO_EDITOR or `editor` in a config file",
+            )
+        })?;
+
+    if verbose {
+        eprintln!("Using editor {:?} from {}", setting.value, setting.source);
+    }
+
+    let mut editor = parse_editor(&setting)?;
+    if let Some(template) = &config.line_template {
+        editor.line_template = Some(parse_line_template(template)?);
+    }
+
+    Ok(editor)
+}
+
+/// Finds an editor when none is configured: git's `core.editor`, then the system's
+/// `sensible-editor`, then the first of [`PROBED_EDITORS`] on `PATH`. The desktop's
+/// file opener comes last, as it may open directories in a file manager rather than an editor.
+pub fn detect_editor() -> Option<Setting<String>> {
+    let git_editor = Command::new("git")
+        .args(["config", "--get", "core.editor"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|editor| !editor.is_empty());
+    if let Some(value) = git_editor {
+        return Some(Setting {
+            value,
+            source: Source::Git,
+        });
+    }
+
+    let openers: &[&str] = if cfg!(target_os = "macos") {
+        &["open"]
+    } else {
+        &["xdg-open"]
+    };
+
+    std::iter::once("sensible-editor")
+        .chain(PROBED_EDITORS.iter().copied())
+        .chain(openers.iter().copied())
+        .find_map(|name| {
+            let path = find_on_path(name)?;
+            Some(Setting {
+                value: name.to_string(),
+                source: Source::Path(path),
+            })
+        })
+}
+
+/// Looks for an executable in the directories on `PATH`.
+fn find_on_path(name: &str) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) {
+        &[".exe", ".cmd", ".bat"]
+    } else {
+        &[""]
+    };
+
+    std::env::split_paths(&std::env::var_os("PATH")?).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|extension| dir.join(format!("{}{}", name, extension)))
+            .find(|path| is_executable(path))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Splits an editor setting into words the way a POSIX shell would,
+/// so values like `code --wait` or `emacsclient -nw -a ''` work as they do for git and cargo.
+fn parse_editor(setting: &Setting<String>) -> Result<Editor, Error> {
+    let mut words = split_words(setting)?.into_iter();
+    let program = words
+        .next()
+        .filter(|program| !program.is_empty())
+        .ok_or_else(|| {
+            Error::raw(
+                ErrorKind::InvalidValue,
+                format!(
+                    "Empty editor command {:?} in {}",
+                    setting.value, setting.source
+                ),
+            )
+        })?;
+
+    Ok(Editor {
+        program: PathBuf::from(program),
+        args: words.collect(),
+        line_template: None,
+    })
+}
+
+/// Splits a line template into words, checking it says where the file goes.
+fn parse_line_template(setting: &Setting<String>) -> Result<Vec<String>, Error> {
+    let words = split_words(setting)?;
+
+    if words.first().is_none_or(|program| program.is_empty()) {
+        return Err(Error::raw(
+            ErrorKind::InvalidValue,
+            format!(
+                "Empty line template {:?} in {}",
+                setting.value, setting.source
+            ),
+        ));
+    }
+    if !words.iter().any(|word| word.contains("{file}")) {
+        return Err(Error::raw(
+            ErrorKind::InvalidValue,
+            format!(
+                "Line template {:?} in {} has no {{file}}",
+                setting.value, setting.source
+            ),
+        ));
+    }
+
+    Ok(words)
+}
+
+fn split_words(setting: &Setting<String>) -> Result<Vec<String>, Error> {
+    shell_words::split(&setting.value).map_err(|e| {
+        Error::raw(
+            ErrorKind::InvalidValue,
+            format!(
+                "Cannot parse editor command {:?} in {}: {}",
+                setting.value, setting.source, e
+            ),
+        )
+    })
+}
+
+/// Opens all of `targets` in one editor.
+pub fn run_editor(editor: &Editor, targets: &[Target]) -> Result<Child, Error> {
+    let mut cmd = editor.command(targets);
+
+    cmd.spawn().map_err(|e| {
+        Error::raw(
+            ErrorKind::Io,
+            format!(
+                "Cannot execute editor: {}: {}",
+                cmd.get_program().to_string_lossy(),
+                e
+            ),
+        )
+    })
+}
+
+/// The editor currently being waited on, or 0 when there isn't one.
+#[cfg(unix)]
+static EDITOR_PID: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+/// Set while a [`DeferredSignals`] guard is alive.
+#[cfg(unix)]
+static DEFERRING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// The last signal held back while deferring, or 0 if there wasn't one.
+#[cfg(unix)]
+static DEFERRED_SIGNAL: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+/// Holds back signals that would end cargo-open until dropped, see [`defer_signals`].
+pub struct DeferredSignals {
+    _private: (),
+}
+
+impl Drop for DeferredSignals {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            use std::sync::atomic::Ordering;
+
+            DEFERRING.store(false, Ordering::SeqCst);
+            let signal = DEFERRED_SIGNAL.swap(0, Ordering::SeqCst);
+            if signal != 0 {
+                let _ = signal_hook::low_level::emulate_default_handler(signal);
+            }
+        }
+    }
+}
+
+/// Holds back signals that would end cargo-open, other than those forwarded to a running
+/// editor, until the returned guard is dropped, then acts on the last one received. This keeps
+/// cleanup, like giving back the permissions taken by [`crate::readonly::protect`], from being
+/// cut short.
+pub fn defer_signals() -> Result<DeferredSignals, Error> {
+    #[cfg(unix)]
+    {
+        install_signal_handlers()?;
+        DEFERRING.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    Ok(DeferredSignals { _private: () })
+}
+
+/// Waits for the editor to exit. Terminal-generated interrupts already reach the editor
+/// through the foreground process group, so they are ignored here, while termination
+/// signals sent to cargo-open alone are forwarded on.
+#[cfg(unix)]
+pub fn wait_editor(mut child: Child) -> Result<ExitStatus, Error> {
+    use std::sync::atomic::Ordering;
+
+    install_signal_handlers()?;
+    EDITOR_PID.store(child.id() as i32, Ordering::SeqCst);
+    let status = child.wait();
+    EDITOR_PID.store(0, Ordering::SeqCst);
+
+    status.map_err(|e| Error::raw(ErrorKind::Io, format!("Cannot wait for editor: {}", e)))
+}
+
+/// Installs the handlers `wait_editor` and `defer_signals` rely on, once for the whole run.
+/// Removing them again wouldn't bring back the default actions, so while no editor is running
+/// and nothing is deferred they emulate those instead, leaving cargo-open as easy to interrupt
+/// as before.
+#[cfg(unix)]
+fn install_signal_handlers() -> Result<(), Error> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+    use signal_hook::low_level::{emulate_default_handler, register};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static INSTALLED: AtomicBool = AtomicBool::new(false);
+    if INSTALLED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    for signal in [SIGINT, SIGQUIT, SIGTERM, SIGHUP] {
+        let action = move || {
+            let pid = EDITOR_PID.load(Ordering::SeqCst);
+            if pid != 0 {
+                if signal == SIGTERM || signal == SIGHUP {
+                    unsafe { libc::kill(pid, signal) };
+                }
+            } else if DEFERRING.load(Ordering::SeqCst) {
+                DEFERRED_SIGNAL.store(signal, Ordering::SeqCst);
+            } else {
+                let _ = emulate_default_handler(signal);
+            }
+        };
+        // Only async-signal-safe atomics, kill and the default handler emulation are used
+        unsafe { register(signal, action) }.map_err(|e| {
+            Error::raw(
+                ErrorKind::Io,
+                format!("Cannot install signal handlers: {}", e),
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn wait_editor(mut child: Child) -> Result<ExitStatus, Error> {
+    child
+        .wait()
+        .map_err(|e| Error::raw(ErrorKind::Io, format!("Cannot wait for editor: {}", e)))
+}
+
+/// Maps the editor's exit status onto ours, using the shell's `128 + signal`
+/// convention when the editor was killed by a signal.
+pub fn exit_code(status: ExitStatus) -> ExitCode {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return ExitCode::from((128 + signal) as u8);
+        }
+    }
+
+    match status.code() {
+        Some(0) => ExitCode::SUCCESS,
+        Some(code) => ExitCode::from(code as u8),
+        None => ExitCode::FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setting(value: &str) -> Setting<String> {
+        Setting {
+            value: value.to_string(),
+            source: Source::Env("CARGO_EDITOR"),
+        }
+    }
+
+    fn editor(command: &str, line_template: Option<&str>) -> Editor {
+        let mut editor = parse_editor(&setting(command)).unwrap();
+        editor.line_template =
+            line_template.map(|template| parse_line_template(&setting(template)).unwrap());
+        editor
+    }
+
+    fn target(path: &str, line: Option<usize>) -> Target {
+        Target {
+            path: PathBuf::from(path),
+            position: line.map(|line| Position { line, column: 5 }),
+        }
+    }
+
+    /// The command line that opens `targets`, program first.
+    fn command_line(editor: &Editor, targets: &[Target]) -> Vec<String> {
+        let cmd = editor.command(targets);
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn splits_editor_commands_into_words() {
+        let editor = editor("'my editor' -nw --flag=\"a b\"", None);
+        assert_eq!(editor.program, PathBuf::from("my editor"));
+        assert_eq!(editor.args, ["-nw", "--flag=a b"]);
+
+        assert!(parse_editor(&setting("")).is_err());
+        assert!(parse_editor(&setting("'unterminated")).is_err());
+    }
+
+    #[test]
+    fn uses_adapter_templates() {
+        let targets = [target("a.rs", Some(3)), target("dir", None)];
+        assert_eq!(
+            command_line(&editor("code --wait", None), &targets),
+            ["code", "--wait", "-g", "a.rs:3:5", "dir"]
+        );
+        assert_eq!(
+            command_line(&editor("/usr/bin/nano", None), &targets),
+            ["/usr/bin/nano", "+3,5", "a.rs", "dir"]
+        );
+        assert_eq!(
+            command_line(&editor("unknown-editor", None), &targets),
+            ["unknown-editor", "+3", "a.rs", "dir"]
+        );
+    }
+
+    #[test]
+    fn repeats_line_templates_after_the_editor() {
+        let targets = [target("a.rs", Some(3)), target("b.rs", Some(7))];
+        let editor = editor("code --wait", Some("env X=1 {editor} --goto {file}:{line}"));
+        assert_eq!(
+            command_line(&editor, &targets),
+            ["env", "X=1", "code", "--wait", "--goto", "a.rs:3", "--goto", "b.rs:7"]
+        );
+    }
+
+    #[test]
+    fn repeats_line_templates_without_the_editor() {
+        let targets = [target("a.rs", Some(3)), target("dir", None), target("b.rs", Some(7))];
+        let editor = editor("code", Some("myedit --line {line} {file}"));
+        assert_eq!(
+            command_line(&editor, &targets),
+            ["myedit", "--line", "3", "a.rs", "dir", "--line", "7", "b.rs"]
+        );
+
+        // Without any positions, the template isn't needed
+        assert_eq!(command_line(&editor, &[target("dir", None)]), ["code", "dir"]);
+    }
+
+    #[test]
+    fn rejects_line_templates_without_a_file() {
+        assert!(parse_line_template(&setting("code --goto {line}")).is_err());
+        assert!(parse_line_template(&setting("")).is_err());
+    }
+
+    #[test]
+    fn waits_for_terminal_editors() {
+        assert!(editor("vim", None).waits_by_default());
+        assert!(!editor("code", None).waits_by_default());
+        assert!(editor("code --wait", None).waits_by_default());
+    }
+}
@@ -0,0 +1,85 @@
+//! Walking, copying and atomically creating directory trees.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// How [`copy_dir`] copies a directory.
+pub struct CopyOptions<'a> {
+    /// Paths relative to the source to leave out, along with everything beneath them.
+    pub skip: &'a [&'a str],
+    /// Whether to make the copied files read-only.
+    pub read_only: bool,
+}
+
+/// Creates the directory `dest` by having `build` write it, under the same name, into a staging
+/// directory beside it, then moving it into place. Whatever an interrupted build left behind stays
+/// in the staging directory and is cleared out next time, so a half-written `dest` is never
+/// mistaken for a complete one.
+pub fn stage_dir(dest: &Path, build: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<()> {
+    let name = dest.file_name().unwrap_or_default();
+    let staging = dest
+        .parent()
+        .unwrap_or(dest)
+        .join(format!(".{}.partial", name.to_string_lossy()));
+    let _ = fs::remove_dir_all(&staging);
+    fs::create_dir_all(&staging)?;
+
+    build(&staging)?;
+
+    let _ = fs::remove_dir_all(dest);
+    fs::rename(staging.join(name), dest)?;
+    let _ = fs::remove_dir_all(&staging);
+    Ok(())
+}
+
+/// Copies the directory `src` to `dest` with [`stage_dir`], replacing anything already there.
+pub fn copy_dir(src: &Path, dest: &Path, options: &CopyOptions) -> io::Result<()> {
+    stage_dir(dest, |staging| {
+        let copy = staging.join(dest.file_name().unwrap_or_default());
+
+        for path in walk(src) {
+            let relative = path.strip_prefix(src).unwrap_or(&path);
+            if options.skip.iter().any(|skip| relative.starts_with(skip)) {
+                continue;
+            }
+
+            let to = copy.join(relative);
+            if path.is_dir() {
+                fs::create_dir_all(&to)?;
+            } else {
+                fs::copy(&path, &to)?;
+                if options.read_only {
+                    let mut permissions = fs::metadata(&to)?.permissions();
+                    permissions.set_readonly(true);
+                    fs::set_permissions(&to, permissions)?;
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Lists `root` and everything beneath it, parents before children, leaving out git metadata.
+pub fn walk(root: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![root.to_path_buf()];
+    let mut index = 0;
+
+    while let Some(dir) = paths.get(index).cloned() {
+        index += 1;
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            if !(is_dir && entry.file_name() == ".git") {
+                paths.push(entry.path());
+            }
+        }
+    }
+
+    paths
+}
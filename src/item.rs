@@ -0,0 +1,477 @@
+//! Finding where an item like `serde::de::Deserialize` is defined within a crate's source.
+
+use crate::editor::Position;
+use clap::{error::ErrorKind, Error};
+use std::path::{Path, PathBuf};
+use syn::{Ident, Item, UseTree};
+
+/// How many `pub use` re-exports to follow before giving up.
+const MAX_REEXPORTS: usize = 8;
+
+/// Where an item is defined.
+pub struct Location {
+    pub file: PathBuf,
+    pub position: Position,
+}
+
+/// A module being searched, with the items it contains and the directory its child modules live in.
+struct Module {
+    file: PathBuf,
+    dir: PathBuf,
+    items: Vec<Item>,
+}
+
+/// Splits a name like `serde::de::Deserialize` into the crate name and the path of the item
+/// within it, if there is one.
+pub fn split_item_path(name: &str) -> (&str, Option<&str>) {
+    match name.split_once("::") {
+        Some((crate_name, item_path)) => (crate_name, Some(item_path)),
+        None => (name, None),
+    }
+}
+
+/// Finds the definition of the item at `item_path` within the crate rooted at `root`,
+/// e.g. `de::Deserialize` for `serde::de::Deserialize`.
+pub fn find_item(root: &Path, item_path: &str) -> Result<Location, Error> {
+    let root = Module::load(root.to_path_buf(), true)?;
+    let path: Vec<&str> = item_path.split("::").collect();
+    let mut external = Vec::new();
+
+    let mut location = resolve(&root, &root, &path, MAX_REEXPORTS, &mut external)?;
+    if let (None, [name]) = (&location, path.as_slice()) {
+        location = find_exported_macro(&root, name)?;
+    }
+
+    location.ok_or_else(|| {
+        let mut message = format!("Item not found: {}", item_path);
+        if !external.is_empty() {
+            message.push_str(&format!(
+                "\nIt may be re-exported from another crate, which isn't searched: {}",
+                external.join(", ")
+            ));
+        }
+        Error::raw(ErrorKind::InvalidValue, message)
+    })
+}
+
+/// Walks `path` from `module`, following `pub use` re-exports, including globs, along the way.
+/// Re-exports from other crates can't be followed, so they're collected in `external` instead.
+fn resolve(
+    root: &Module,
+    module: &Module,
+    path: &[&str],
+    reexports: usize,
+    external: &mut Vec<String>,
+) -> Result<Option<Location>, Error> {
+    let (name, rest) = match path {
+        [] => return Ok(None),
+        [name, rest @ ..] => (*name, rest),
+    };
+
+    if rest.is_empty() {
+        if let Some(location) = module.find_definition(name) {
+            return Ok(Some(location));
+        }
+    } else if let Some(child) = module.child(name)? {
+        return resolve(root, &child, rest, reexports, external);
+    }
+
+    if reexports == 0 {
+        return Ok(None);
+    }
+
+    let globs = module.glob_reexports().into_iter().map(|mut prefix| {
+        prefix.push(name.to_string());
+        prefix
+    });
+    for target in module.reexports(name).into_iter().chain(globs) {
+        let mut target: Vec<&str> = target.iter().map(String::as_str).collect();
+        target.extend(rest);
+
+        let location = match target.as_slice() {
+            ["crate", target @ ..] => resolve(root, root, target, reexports - 1, external)?,
+            ["self", target @ ..] => resolve(root, module, target, reexports - 1, external)?,
+            ["super", ..] => None,
+            [first, ..] if !module.has_local(first)? => {
+                // Anything else not named in this module comes from another crate
+                let target = target.join("::");
+                if !external.contains(&target) {
+                    external.push(target);
+                }
+                None
+            }
+            target => resolve(root, module, target, reexports - 1, external)?,
+        };
+        if location.is_some() {
+            return Ok(location);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Searches every module of the crate for a `#[macro_export]` macro named `name`,
+/// as those are exported from the crate root wherever they're defined.
+fn find_exported_macro(module: &Module, name: &str) -> Result<Option<Location>, Error> {
+    let location = module.items.iter().find_map(|item| match item {
+        Item::Macro(mac)
+            if mac.mac.path.is_ident("macro_rules")
+                && mac.attrs.iter().any(|attr| attr.path().is_ident("macro_export")) =>
+        {
+            let ident = mac.ident.as_ref().filter(|ident| *ident == name)?;
+            Some(module.location(item, ident))
+        }
+        _ => None,
+    });
+    if location.is_some() {
+        return Ok(location);
+    }
+
+    let children: Vec<&Ident> = module
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Mod(item) => Some(&item.ident),
+            _ => None,
+        })
+        .collect();
+
+    for child in children {
+        if let Some(child) = module.child(&child.to_string())? {
+            if let Some(location) = find_exported_macro(&child, name)? {
+                return Ok(Some(location));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+impl Module {
+    /// Parses the module in `file`. Crate roots and `mod.rs` files own their directory,
+    /// while other files keep their child modules in a directory named after themselves.
+    fn load(file: PathBuf, owns_dir: bool) -> Result<Self, Error> {
+        let source = std::fs::read_to_string(&file).map_err(|e| {
+            Error::raw(
+                ErrorKind::Io,
+                format!("Cannot read {}: {}", file.display(), e),
+            )
+        })?;
+        let items = syn::parse_file(&source)
+            .map_err(|e| {
+                Error::raw(
+                    ErrorKind::Io,
+                    format!("Cannot parse {}: {}", file.display(), e),
+                )
+            })?
+            .items;
+
+        let parent = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        let dir = if owns_dir || file.file_name().is_some_and(|name| name == "mod.rs") {
+            parent
+        } else {
+            parent.join(file.file_stem().unwrap_or_default())
+        };
+
+        Ok(Module { file, dir, items })
+    }
+
+    /// The child module named `name`, whether inline, in `name.rs`, `name/mod.rs` or a `#[path]`.
+    /// Modules declared more than once under different `cfg`s resolve to the first that exists.
+    fn child(&self, name: &str) -> Result<Option<Module>, Error> {
+        let declarations = self.items.iter().filter_map(|item| match item {
+            Item::Mod(item) if item.ident == name => Some(item),
+            _ => None,
+        });
+
+        for declaration in declarations {
+            if let Some((_, items)) = &declaration.content {
+                return Ok(Some(Module {
+                    file: self.file.clone(),
+                    dir: self.dir.join(name),
+                    items: items.clone(),
+                }));
+            }
+
+            let path_attr = declaration.attrs.iter().find_map(|attr| {
+                let syn::Meta::NameValue(meta) = &attr.meta else {
+                    return None;
+                };
+                let syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(path),
+                    ..
+                }) = &meta.value
+                else {
+                    return None;
+                };
+                meta.path.is_ident("path").then(|| path.value())
+            });
+
+            if let Some(path) = path_attr {
+                let file = self.file.parent().unwrap_or(&self.dir).join(path);
+                if file.is_file() {
+                    return Module::load(file, true).map(Some);
+                }
+                continue;
+            }
+
+            let file = self.dir.join(format!("{}.rs", name));
+            if file.is_file() {
+                return Module::load(file, false).map(Some);
+            }
+
+            let file = self.dir.join(name).join("mod.rs");
+            if file.is_file() {
+                return Module::load(file, true).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The item named `name` defined directly in this module.
+    fn find_definition(&self, name: &str) -> Option<Location> {
+        self.items.iter().find_map(|item| {
+            let ident = match item {
+                Item::Const(item) => &item.ident,
+                Item::Enum(item) => &item.ident,
+                Item::Fn(item) => &item.sig.ident,
+                Item::Macro(item) if item.mac.path.is_ident("macro_rules") => item.ident.as_ref()?,
+                Item::Mod(item) => &item.ident,
+                Item::Static(item) => &item.ident,
+                Item::Struct(item) => &item.ident,
+                Item::Trait(item) => &item.ident,
+                Item::TraitAlias(item) => &item.ident,
+                Item::Type(item) => &item.ident,
+                Item::Union(item) => &item.ident,
+                _ => return None,
+            };
+
+            (ident == name).then(|| self.location(item, ident))
+        })
+    }
+
+    /// Modules declared in their own file are opened at the top of that file,
+    /// everything else at the line of its name.
+    fn location(&self, item: &Item, ident: &Ident) -> Location {
+        if let Item::Mod(item) = item {
+            if item.content.is_none() {
+                if let Ok(Some(child)) = self.child(&ident.to_string()) {
+                    return Location {
+                        file: child.file,
+                        position: Position { line: 1, column: 1 },
+                    };
+                }
+            }
+        }
+
+        let start = ident.span().start();
+        Location {
+            file: self.file.clone(),
+            position: Position {
+                line: start.line,
+                column: start.column + 1,
+            },
+        }
+    }
+
+    /// Whether `name` is defined, declared as a module or imported in this module,
+    /// rather than being another crate.
+    fn has_local(&self, name: &str) -> Result<bool, Error> {
+        let imported = self.items.iter().any(|item| match item {
+            Item::Use(item) => {
+                let mut paths = Vec::new();
+                collect_use_paths(&item.tree, &mut Vec::new(), name, &mut paths);
+                !paths.is_empty()
+            }
+            _ => false,
+        });
+        Ok(imported || self.find_definition(name).is_some() || self.child(name)?.is_some())
+    }
+
+    /// The modules that `pub use <path>::*` declarations in this module re-export everything from.
+    fn glob_reexports(&self) -> Vec<Vec<String>> {
+        let mut paths = Vec::new();
+        for item in &self.items {
+            if let Item::Use(item) = item {
+                if matches!(item.vis, syn::Visibility::Public(_)) {
+                    collect_glob_paths(&item.tree, &mut Vec::new(), &mut paths);
+                }
+            }
+        }
+        paths
+    }
+
+    /// The paths that `pub use` declarations in this module re-export as `name`.
+    fn reexports(&self, name: &str) -> Vec<Vec<String>> {
+        let mut paths = Vec::new();
+        for item in &self.items {
+            if let Item::Use(item) = item {
+                if matches!(item.vis, syn::Visibility::Public(_)) {
+                    collect_use_paths(&item.tree, &mut Vec::new(), name, &mut paths);
+                }
+            }
+        }
+        paths
+    }
+}
+
+/// Collects the full paths in a use tree that are imported as `name`.
+fn collect_use_paths(
+    tree: &UseTree,
+    prefix: &mut Vec<String>,
+    name: &str,
+    paths: &mut Vec<Vec<String>>,
+) {
+    match tree {
+        UseTree::Path(tree) => {
+            prefix.push(tree.ident.to_string());
+            collect_use_paths(&tree.tree, prefix, name, paths);
+            prefix.pop();
+        }
+        UseTree::Name(tree) if tree.ident == name => {
+            paths.push([prefix.as_slice(), &[name.to_string()]].concat());
+        }
+        UseTree::Rename(tree) if tree.rename == name => {
+            paths.push([prefix.as_slice(), &[tree.ident.to_string()]].concat());
+        }
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                collect_use_paths(tree, prefix, name, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Collects the paths of the modules that a use tree imports everything from.
+fn collect_glob_paths(tree: &UseTree, prefix: &mut Vec<String>, paths: &mut Vec<Vec<String>>) {
+    match tree {
+        UseTree::Path(tree) => {
+            prefix.push(tree.ident.to_string());
+            collect_glob_paths(&tree.tree, prefix, paths);
+            prefix.pop();
+        }
+        UseTree::Glob(_) => paths.push(prefix.clone()),
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                collect_glob_paths(tree, prefix, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Writes a crate's files into a fresh directory, returning the path of its `lib.rs`.
+    fn write_crate(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cargo-open-item-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir.join("lib.rs")
+    }
+
+    /// Finds `path` in the crate, as the file relative to the crate and the line and column.
+    fn find(lib: &Path, path: &str) -> Result<(String, usize, usize), Error> {
+        let location = find_item(lib, path)?;
+        let file = location.file.strip_prefix(lib.parent().unwrap()).unwrap();
+        let file = file.to_string_lossy().replace('\\', "/");
+        Ok((file, location.position.line, location.position.column))
+    }
+
+    #[test]
+    fn resolves_module_files() {
+        let lib = write_crate(
+            "modules",
+            &[
+                (
+                    "lib.rs",
+                    "pub mod a;\npub mod c;\n#[path = \"other.rs\"]\npub mod e;\n\
+                     pub mod f {\n    pub mod g;\n}\n",
+                ),
+                ("a.rs", "pub mod b;\n"),
+                ("a/b.rs", "pub struct B;\n"),
+                ("c/mod.rs", "pub mod d;\n"),
+                ("c/d.rs", "\npub fn d() {}\n"),
+                ("other.rs", "pub enum E {}\n"),
+                ("f/g.rs", "pub trait G {}\n"),
+            ],
+        );
+
+        assert_eq!(find(&lib, "a::b::B").unwrap(), ("a/b.rs".into(), 1, 12));
+        assert_eq!(find(&lib, "c::d::d").unwrap(), ("c/d.rs".into(), 2, 8));
+        assert_eq!(find(&lib, "e::E").unwrap(), ("other.rs".into(), 1, 10));
+        assert_eq!(find(&lib, "f::g::G").unwrap(), ("f/g.rs".into(), 1, 11));
+        // Modules in their own file open at the top of it
+        assert_eq!(find(&lib, "a").unwrap(), ("a.rs".into(), 1, 1));
+        assert_eq!(find(&lib, "f").unwrap(), ("lib.rs".into(), 5, 9));
+    }
+
+    #[test]
+    fn follows_reexports() {
+        let lib = write_crate(
+            "reexports",
+            &[
+                (
+                    "lib.rs",
+                    "mod inner;\npub use crate::inner::{Inner, Other as Renamed};\n\
+                     pub use self::inner::nested::*;\n",
+                ),
+                (
+                    "inner.rs",
+                    "pub struct Inner;\npub struct Other;\n\
+                     pub mod nested {\n    pub fn nested() {}\n}\n",
+                ),
+            ],
+        );
+
+        assert_eq!(find(&lib, "Inner").unwrap(), ("inner.rs".into(), 1, 12));
+        assert_eq!(find(&lib, "Renamed").unwrap(), ("inner.rs".into(), 2, 12));
+        assert_eq!(find(&lib, "nested").unwrap(), ("inner.rs".into(), 4, 12));
+    }
+
+    #[test]
+    fn finds_exported_macros_at_the_root() {
+        let lib = write_crate(
+            "macros",
+            &[
+                ("lib.rs", "mod macros;\n"),
+                (
+                    "macros.rs",
+                    "#[macro_export]\nmacro_rules! exported { () => {} }\n\
+                     macro_rules! private { () => {} }\n",
+                ),
+            ],
+        );
+
+        assert_eq!(find(&lib, "exported").unwrap(), ("macros.rs".into(), 2, 14));
+        assert!(find(&lib, "private").is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_paths() {
+        let lib = write_crate(
+            "wrong",
+            &[("lib.rs", "pub mod a {\n    pub struct A;\n}\npub use proc_macro2::Ident;\n")],
+        );
+
+        // The item exists, but not at that path
+        assert!(find(&lib, "b::A").is_err());
+        assert!(find(&lib, "A").is_err());
+
+        let error = find(&lib, "Ident").unwrap_err().to_string();
+        assert!(error.contains("proc_macro2::Ident"), "{}", error);
+    }
+}
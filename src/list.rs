@@ -0,0 +1,133 @@
+//! Listing the packages in the dependency graph, filtered by how they're depended on.
+
+use cargo_metadata::{DependencyKind, Metadata, Package, PackageId};
+use clap::ValueEnum;
+use std::collections::{HashMap, HashSet};
+
+/// The kind of dependency a package is pulled in as, following cargo's manifest sections.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Kind {
+    /// `[dependencies]`, and everything they depend on
+    Normal,
+    /// `[dev-dependencies]`, and everything they depend on
+    Dev,
+    /// `[build-dependencies]`, and everything they or any other dependency's build scripts depend on
+    Build,
+}
+
+/// Which packages to list. Unset filters let everything through.
+#[derive(Default)]
+pub struct Filter {
+    pub direct: bool,
+    pub transitive: bool,
+    pub kinds: Vec<Kind>,
+    pub workspace: bool,
+    pub no_workspace: bool,
+}
+
+/// How a package is reached from the workspace members.
+#[derive(Default)]
+struct Usage {
+    direct: bool,
+    kinds: HashSet<Kind>,
+}
+
+/// The packages in `metadata` that pass `filter`, sorted by name and version.
+pub fn list_packages<'a>(metadata: &'a Metadata, filter: &Filter) -> Vec<&'a Package> {
+    let members: HashSet<&PackageId> = metadata.workspace_members.iter().collect();
+    let usages = usages(metadata, &members);
+
+    let mut packages: Vec<&Package> = metadata
+        .packages
+        .iter()
+        .filter(|package| {
+            let is_member = members.contains(&package.id);
+            if (filter.workspace && !is_member) || (filter.no_workspace && is_member) {
+                return false;
+            }
+
+            let usage = usages.get(&package.id);
+            let is_direct = usage.is_some_and(|usage| usage.direct);
+            if (filter.direct && !is_direct) || (filter.transitive && (is_direct || is_member)) {
+                return false;
+            }
+
+            filter.kinds.is_empty()
+                || filter
+                    .kinds
+                    .iter()
+                    .any(|kind| usage.is_some_and(|usage| usage.kinds.contains(kind)))
+        })
+        .collect();
+
+    packages.sort_by(|a, b| a.name.cmp(&b.name).then(a.version.cmp(&b.version)));
+    packages
+}
+
+/// Walks the resolve graph from each workspace member, recording whether each package is a
+/// direct dependency and which kinds of dependency it's reached through. Build dependencies
+/// make everything beneath them build dependencies too, while other edges keep the kind of
+/// the member's dependency they were reached from.
+fn usages<'a>(
+    metadata: &'a Metadata,
+    members: &HashSet<&'a PackageId>,
+) -> HashMap<&'a PackageId, Usage> {
+    let mut usages: HashMap<&PackageId, Usage> = HashMap::new();
+    let Some(resolve) = &metadata.resolve else {
+        return usages;
+    };
+
+    let nodes: HashMap<&PackageId, _> = resolve
+        .nodes
+        .iter()
+        .map(|node| (&node.id, node))
+        .collect();
+
+    let mut seen: HashSet<(&PackageId, Kind)> = HashSet::new();
+    let mut stack: Vec<(&PackageId, Kind)> = Vec::new();
+
+    for member in members {
+        let Some(node) = nodes.get(member) else {
+            continue;
+        };
+
+        for dep in &node.deps {
+            for dep_kind in &dep.dep_kinds {
+                let kind = match dep_kind.kind {
+                    DependencyKind::Development => Kind::Dev,
+                    DependencyKind::Build => Kind::Build,
+                    _ => Kind::Normal,
+                };
+
+                usages.entry(&dep.pkg).or_default().direct = true;
+                if seen.insert((&dep.pkg, kind)) {
+                    stack.push((&dep.pkg, kind));
+                }
+            }
+        }
+    }
+
+    while let Some((id, kind)) = stack.pop() {
+        usages.entry(id).or_default().kinds.insert(kind);
+
+        let Some(node) = nodes.get(id) else {
+            continue;
+        };
+
+        for dep in &node.deps {
+            for dep_kind in &dep.dep_kinds {
+                let kind = match dep_kind.kind {
+                    DependencyKind::Build => Kind::Build,
+                    DependencyKind::Development => continue,
+                    _ => kind,
+                };
+
+                if seen.insert((&dep.pkg, kind)) {
+                    stack.push((&dep.pkg, kind));
+                }
+            }
+        }
+    }
+
+    usages
+}
@@ -0,0 +1,252 @@
+//! A fast path that finds a package's source directory from `Cargo.lock` and the
+//! layout of `$CARGO_HOME`, without waiting for `cargo metadata` to resolve the whole workspace.
+//!
+//! Anything out of the ordinary returns `None`, leaving it to the full metadata lookup.
+
+use crate::spec::{self, PackageSpec};
+use cargo_metadata::semver::Version;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// How deep to look for a package's manifest within a git checkout.
+const MAX_CHECKOUT_DEPTH: usize = 4;
+
+#[derive(Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+#[derive(Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: Version,
+    /// Missing for workspace members and path dependencies.
+    source: Option<String>,
+}
+
+/// The parts of the workspace's root manifest needed to find its members.
+#[derive(Deserialize)]
+struct Manifest {
+    workspace: Option<Workspace>,
+}
+
+#[derive(Deserialize)]
+struct Workspace {
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+/// Finds the extracted source of the one locked package matching `spec`.
+pub fn find_package_dir(manifest_path: Option<&Path>, spec: &PackageSpec) -> Option<PathBuf> {
+    let lockfile_path = find_lockfile(manifest_path)?;
+    if is_stale(&lockfile_path, manifest_path) {
+        return None;
+    }
+
+    let lockfile: Lockfile = toml::from_str(&fs::read_to_string(&lockfile_path).ok()?).ok()?;
+    let mut matches = lockfile.package.iter().filter(|package| {
+        let source = package.source.as_deref().unwrap_or_default();
+        spec.matches_locked(&package.name, &package.version, source)
+    });
+
+    let package = matches.next()?;
+    if matches.next().is_some() {
+        return None;
+    }
+
+    let source = package.source.as_deref()?;
+    let cargo_home = cargo_home()?;
+    if let Some(url) = source
+        .strip_prefix("registry+")
+        .or_else(|| source.strip_prefix("sparse+"))
+    {
+        find_registry_dir(&cargo_home, url, package)
+    } else if let Some(url) = source.strip_prefix("git+") {
+        find_git_dir(&cargo_home, url, package)
+    } else {
+        None
+    }
+}
+
+pub fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".cargo")))
+}
+
+/// Looks for `Cargo.lock` beside the manifest, or in the closest directory above it that has one,
+/// the same place cargo keeps it for a workspace.
+fn find_lockfile(manifest_path: Option<&Path>) -> Option<PathBuf> {
+    let start = match manifest_path {
+        Some(manifest_path) => fs::canonicalize(manifest_path).ok()?.parent()?.to_path_buf(),
+        None => std::env::current_dir().ok()?,
+    };
+
+    start
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|lockfile| lockfile.is_file() && lockfile.with_file_name("Cargo.toml").is_file())
+}
+
+/// A lockfile older than the workspace's manifests, or the one given on the command line,
+/// may not reflect dependencies added since, so can't be trusted.
+fn is_stale(lockfile: &Path, manifest_path: Option<&Path>) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    let Some(locked_at) = modified(lockfile) else {
+        return true;
+    };
+
+    let root_manifest = lockfile.with_file_name("Cargo.toml");
+    let Some(members) = member_manifests(&root_manifest) else {
+        return true;
+    };
+    let current_manifest = std::env::current_dir().ok().map(|dir| dir.join("Cargo.toml"));
+    let manifests = [
+        Some(root_manifest),
+        manifest_path.map(Path::to_path_buf),
+        current_manifest,
+    ];
+
+    manifests
+        .into_iter()
+        .flatten()
+        .chain(members)
+        .filter_map(|manifest| modified(&manifest))
+        .any(|manifest_modified| manifest_modified > locked_at)
+}
+
+/// The manifests of the members listed under `[workspace]` in the root manifest, expanding
+/// `*` and `?` globs. `None` if the root manifest can't be read, or uses globs this can't expand.
+fn member_manifests(root_manifest: &Path) -> Option<Vec<PathBuf>> {
+    let manifest: Manifest = toml::from_str(&fs::read_to_string(root_manifest).ok()?).ok()?;
+    let root = root_manifest.parent()?;
+    let Some(workspace) = manifest.workspace else {
+        return Some(Vec::new());
+    };
+
+    let mut manifests = Vec::new();
+    for member in &workspace.members {
+        if member.contains(['[', ']']) {
+            return None;
+        }
+
+        let mut dirs = vec![root.to_path_buf()];
+        for component in member.split('/') {
+            dirs = if component.contains(['*', '?']) {
+                dirs.iter()
+                    .flat_map(|dir| read_dirs(dir))
+                    .filter(|dir| {
+                        let name = dir.file_name().unwrap_or_default().to_string_lossy();
+                        spec::glob_matches(component, &name)
+                    })
+                    .collect()
+            } else {
+                dirs.into_iter().map(|dir| dir.join(component)).collect()
+            };
+        }
+        manifests.extend(dirs.into_iter().map(|dir| dir.join("Cargo.toml")));
+    }
+
+    Some(manifests)
+}
+
+/// Registry sources are extracted to `registry/src/<index>-<hash>/<name>-<version>`, where the hash
+/// depends on the cargo version, so every index directory for the registry is tried.
+fn find_registry_dir(cargo_home: &Path, url: &str, package: &LockedPackage) -> Option<PathBuf> {
+    let package_dir = format!("{}-{}", package.name, package.version);
+    read_dirs(&cargo_home.join("registry").join("src"))
+        .into_iter()
+        .filter(|index| {
+            let name = index.file_name().unwrap_or_default().to_string_lossy();
+            is_index_dir(url, &name)
+        })
+        .map(|index| index.join(&package_dir))
+        // Cargo writes `.cargo-ok` once a crate is fully extracted
+        .find(|dir| dir.join(".cargo-ok").exists())
+}
+
+/// Whether `dir_name` is one of the `<host>-<hash>` directories cargo keeps the registry at `url`
+/// in, under `registry/src` and `registry/cache`.
+pub fn is_index_dir(url: &str, dir_name: &str) -> bool {
+    let url = url
+        .strip_prefix("registry+")
+        .or_else(|| url.strip_prefix("sparse+"))
+        .unwrap_or(url);
+    let Some(host) = url.split("://").nth(1).and_then(|rest| rest.split('/').next()) else {
+        return false;
+    };
+    // Newer versions of cargo keep crates.io's git index under the name of its sparse one
+    let crates_io = url.trim_end_matches('/') == "https://github.com/rust-lang/crates.io-index";
+    let hosts: &[&str] = if crates_io {
+        &["index.crates.io", "github.com"]
+    } else {
+        &[host]
+    };
+
+    hosts.iter().any(|host| {
+        dir_name
+            .strip_prefix(host)
+            .is_some_and(|rest| rest.starts_with('-'))
+    })
+}
+
+/// Git sources are checked out to `git/checkouts/<repo>-<hash>/<short commit>`,
+/// with the package somewhere within the repository.
+fn find_git_dir(cargo_home: &Path, url: &str, package: &LockedPackage) -> Option<PathBuf> {
+    let (url, commit) = url.split_once('#')?;
+    let url = url.split('?').next()?.trim_end_matches('/');
+    let repo = url.rsplit('/').next()?.trim_end_matches(".git");
+
+    let checkouts = read_dirs(&cargo_home.join("git").join("checkouts"));
+    checkouts
+        .into_iter()
+        .filter(|dir| {
+            let name = dir.file_name().unwrap_or_default().to_string_lossy();
+            name.strip_prefix(repo)
+                .is_some_and(|rest| rest.starts_with('-'))
+        })
+        .flat_map(|dir| read_dirs(&dir))
+        .filter(|checkout| {
+            let short_id = checkout.file_name().unwrap_or_default().to_string_lossy();
+            short_id.len() >= 7 && commit.starts_with(short_id.as_ref())
+        })
+        .find_map(|checkout| find_manifest_dir(&checkout, &package.name, MAX_CHECKOUT_DEPTH))
+}
+
+/// Finds the directory under `dir` whose manifest is for the package `name`.
+fn find_manifest_dir(dir: &Path, name: &str, depth: usize) -> Option<PathBuf> {
+    let manifest = fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| manifest.parse::<toml::Table>().ok());
+    let package_name = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.get("package")?.get("name")?.as_str());
+    if package_name == Some(name) {
+        return Some(dir.to_path_buf());
+    }
+
+    if depth == 0 {
+        return None;
+    }
+
+    read_dirs(dir)
+        .into_iter()
+        .filter(|child| !child.ends_with(".git") && !child.ends_with("target"))
+        .find_map(|child| find_manifest_dir(&child, name, depth - 1))
+}
+
+fn read_dirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect()
+}
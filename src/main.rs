@@ -3,7 +3,8 @@
 //! 
 //! Note that the intended use is to open a crate's source for reading.
 //! Making changes to installed crates is not reccommended, and may produce unexpected results.
-//! Instead, clone the crate locally and [specify the path in your Cargo.toml](https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html#specifying-path-dependencies).
+//! Instead, clone the crate locally and [specify the path in your Cargo.toml](https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html#specifying-path-dependencies),
+//! or let `cargo open --patch` do it for you, as described below.
 //! 
 //! # Installation
 //! 
@@ -19,19 +20,207 @@
 //! cargo open clap
 //! ```
 //! 
+//! When several versions of a crate are in the dependency graph, pick one with a
+//! [package id spec](https://doc.rust-lang.org/cargo/reference/pkgid-spec.html):
+//! 
+//! ```sh
+//! cargo open syn@1.0.109
+//! cargo open syn@^2
+//! cargo open registry+https://github.com/rust-lang/crates.io-index#syn@2.0.63
+//! ```
+//! 
+//! Follow the crate name with a path to open the file defining a particular module or item, at the line it's defined on:
+//! 
+//! ```sh
+//! cargo open serde::de::Deserialize
+//! cargo open tokio::sync
+//! ```
+//! 
+//! Several crates can be opened at once, including with glob patterns.
+//! They're passed to a single editor, so multi-root editors like VS Code, Zed or Sublime Text open them in one window.
+//! Pass `--separate` to open each in its own editor instead:
+//! 
+//! ```sh
+//! cargo open tokio 'tokio-*'
+//! cargo open --separate serde serde_json
+//! ```
+//! 
+//! Crates that aren't dependencies of the current project, or when not in a project at all, can be opened
+//! from the `.crate` archives cargo has already downloaded to `$CARGO_HOME/registry/cache`.
+//! This works offline, against crates.io or a mirror. The newest cached version matching the spec is used,
+//! unpacked into `$CARGO_HOME/cargo-open/src` unless cargo has already extracted it:
+//! 
+//! ```sh
+//! cargo open --registry regex@1.10
+//! ```
+//! 
+//! The standard library crates `std`, `core`, `alloc`, `proc_macro` and `test` are opened from the
+//! active toolchain's sources, following `rust-toolchain.toml` and `cargo +toolchain` overrides.
+//! These need the `rust-src` component, installed with `rustup component add rust-src`:
+//! 
+//! ```sh
+//! cargo open std::collections
+//! cargo +nightly open core
+//! ```
+//! 
+//! Workspace members are preferred over dependencies of the same name, and can also be opened by path,
+//! with `.` opening the member in the current directory. Pass `--workspace-root` to open the workspace's root directory.
+//! Crates can be given with `-p`/`--package` too, as with other cargo commands:
+//! 
+//! ```sh
+//! cargo open .
+//! cargo open -p api-server
+//! cargo open --workspace-root
+//! ```
+//! 
+//! Crate names are matched ignoring case and `-`/`_`, so `serde-json`, `serde_json` and `SerdeJson` all open the same crate.
+//! 
+//! Dependencies can also be opened by the name they're used under in code:
+//! a rename in the current workspace member's manifest, like `tokio1 = { package = "tokio" }`,
+//! or a library target name that differs from the package name.
+//! 
+//! When run in a terminal, an ambiguous or unknown crate name brings up a list of packages to pick from instead.
+//! Use the arrow keys to move, type to filter, and enter to open the highlighted package.
+//! 
+//! To use the crate's directory elsewhere, print it instead of opening an editor.
+//! For item paths, the file defining the item is printed. No editor needs to be configured for this:
+//! 
+//! ```sh
+//! cd $(cargo open --print tokio)
+//! cargo open --print0 serde | xargs -0 rg Deserializer
+//! ```
+//! 
 //! # Configuration
 //! 
 //! The editor command is picked from the `CARGO_EDITOR`, `VISUAL`, or `EDITOR` environment variables,
 //! checked in that order.
+//! The value is split into words like a shell would, so arguments and quoting work as expected:
+//! 
+//! ```sh
+//! export CARGO_EDITOR="code --wait"
+//! export CARGO_EDITOR="emacsclient -nw -a ''"
+//! ```
+//! 
+//! When no editor is configured, git's `core.editor` is used, then `sensible-editor`, then the first of
+//! `code`, `nvim`, `vim`, `hx` and `nano` found on `PATH`, and finally `xdg-open` (or `open` on macOS).
+//! Pass `--verbose` to see which editor was chosen and why.
+//! 
+//! Settings can also be kept in `$XDG_CONFIG_HOME/cargo-open/config.toml` (usually `~/.config/cargo-open/config.toml`),
+//! or in an `[open]` table of cargo's own [config files](https://doc.rust-lang.org/cargo/reference/config.html),
+//! which are discovered from the current directory upwards as cargo does:
+//! 
+//! ```toml
+//! [open]
+//! editor = "code"
+//! line-template = "{editor} --goto {file}:{line}:{column}"
+//! wait = true
+//! format = "json"
+//! 
+//! [open.aliases]
+//! tokio1 = "tokio@1"
+//! api = "./crates/api-server"
+//! ```
+//! 
+//! Closer cargo config files override those further up, which override the cargo-open config file.
+//! Each key can be overridden in turn with a `CARGO_OPEN_*` environment variable, like `CARGO_OPEN_WAIT=false`,
+//! and then by command line options. `CARGO_EDITOR` overrides the config files too, while `VISUAL` and `EDITOR`,
+//! which are set for every program, are only used when no editor is configured.
+//! A configured `format` is ignored when an option such as `--print` or `--workspace-root` asks for something else.
+//! Aliases stand in for crate names, specs or paths on the command line.
+//! Run `cargo open --config-show` to see the effective settings and where each was set.
+//! 
+//! For tooling, `--format json` prints a description of the resolved package instead:
+//! its name, version, id, source kind (`registry`, `git`, `path` or `vendored`), manifest path,
+//! root directory, library source path, edition, license and repository.
+//! The document carries a `schema_version`, which only changes when existing fields are removed or change meaning.
+//! 
+//! To see what can be opened, list the packages in the dependency graph with their source kind and directory.
+//! The list can be narrowed to `--direct` or `--transitive` dependencies, to `--kind normal`, `dev` or `build`
+//! dependencies, and to or away from workspace members with `--workspace` and `--no-workspace`:
+//! 
+//! ```sh
+//! cargo open --list
+//! cargo open --list --direct --kind dev
+//! ```
+//! 
+//! When opening an item, the file and position are passed in the form the editor expects,
+//! e.g. `vim +LINE FILE`, `code -g FILE:LINE:COLUMN` or `idea --line LINE FILE`.
+//! Editors that aren't recognised are passed `+LINE FILE`, which most terminal editors understand.
+//! Set `CARGO_OPEN_LINE_TEMPLATE`, or `line-template` in a config file, to override this:
+//! 
+//! ```sh
+//! export CARGO_OPEN_LINE_TEMPLATE="{editor} --goto {file}:{line}:{column}"
+//! ```
+//! 
+//! When opening several files, everything after `{editor}` is repeated for each one.
+//! 
+//! Terminal editors are waited on, and `cargo open` exits with the editor's exit status.
+//! GUI editors such as VS Code are launched in the background unless `--wait` is given.
+//! Pass `--no-wait` to return immediately regardless.
+//! 
+//! While waiting, the sources of registry and git dependencies, and of the standard library, are checked
+//! for changes. If any files were added, removed or modified by the time the editor exits, they're listed
+//! along with how to restore the originals, since these sources are shared by every project on the machine.
+//! 
+//! To audit the sources cargo has extracted, `--verify` compares registry crates file by file with the
+//! `.crate` archives they came from in `$CARGO_HOME/registry/cache`, reporting added, removed and modified files.
+//! With no crates given, every registry crate in the dependency graph is checked.
+//! The exit status is non-zero if any crate differs from its archive:
+//! 
+//! ```sh
+//! cargo open --verify
+//! cargo open --verify serde tokio
+//! ```
+//! 
+//! To undo changes, `--restore` extracts registry crates from their archives again, removing any added files,
+//! and resets git dependencies' checkouts. Add `--dry-run` to list the files that would be restored first:
+//! 
+//! ```sh
+//! cargo open --restore --dry-run serde
+//! cargo open --restore serde
+//! ```
+//! 
+//! Crates opened from the registry cache with `--registry` are restored with `--registry --restore`.
+//! 
+//! To make accidental saves impossible in the first place, pass `--read-only`. While the editor is
+//! waited on, the crate's files and directories are made read-only, and their permissions are put back
+//! once it exits. Otherwise a read-only copy of the crate, kept in `$CARGO_HOME/cargo-open/read-only`
+//! and refreshed whenever the crate's sources change, is opened instead. This only applies to registry
+//! and git dependencies and the standard library, as workspace members and path dependencies are yours
+//! to edit:
+//! 
+//! ```sh
+//! cargo open --read-only serde
+//! ```
+//! 
+//! To edit a dependency, `--patch` copies it into `patches/<name>-<version>` in the workspace and adds a
+//! [`[patch]`](https://doc.rust-lang.org/cargo/reference/overriding-dependencies.html#the-patch-section) entry
+//! for the copy to the workspace's `Cargo.toml`, leaving the rest of the file's formatting and comments alone,
+//! then opens the copy. Registry crates are patched under `[patch.crates-io]`, or their registry's URL,
+//! and git dependencies under their repository's URL. Patching a crate again just opens the existing copy:
+//! 
+//! ```sh
+//! cargo open --patch serde
+//! ```
+//! 
+//! Plain crate names and package id specs are looked up directly in `Cargo.lock` and opened from cargo's
+//! registry and git caches, without waiting for `cargo metadata`. If the lockfile is missing or older than
+//! the manifest, or the crate isn't in the cache, the full dependency graph is resolved instead.
+//! Where each package lives is then cached in `$CARGO_HOME/cargo-open/cache` until `Cargo.lock` or any
+//! local manifest changes, so path dependencies and workspace members open quickly too.
+//! Pass `--refresh` to ignore the lockfile and cache and resolve the graph again.
 //! 
 //! Specify a different manifest file with the `--manifest-path` option.
 //! By default, `Cargo.toml` in the current directory is used.
 //! 
+//! The `--offline`, `--frozen`, `--locked`, `--features`, `--all-features`, `--no-default-features` and `--filter-platform`
+//! options are passed on to `cargo metadata`, so the dependency graph matches what you build,
+//! including crates behind optional features.
+//! 
 //! # Todo/Contributing
 //! 
-//! There aren't any tests, as this is just glueing together the [cargo-metadata](https://crates.io/crates/cargo_metadata)
-//! crate and some bits from the standard library. As such it should be robust, and what little logic there is should be
-//! adequately constrained by the type system.
+//! Most of this is glue around the [cargo-metadata](https://crates.io/crates/cargo_metadata) crate and the standard
+//! library. The logic of its own, such as parsing package specs, has unit tests alongside it, run with `cargo test`.
 //! 
 //! Regardless, if you have any problems, suggestions or improvements, feel free to create an issue or PR.
 //! 
@@ -42,11 +231,32 @@
 //! The original cargo-open was authored by Carol Nichols ([@carols10cents](https://github.com/carols10cents)), and crate ownership was transferred
 //! in may 2024. Many thanks to Carol for all her work in the rust community.  
 
-use cargo_metadata::{Metadata, MetadataCommand, Package};
-use clap::{error::ErrorKind, CommandFactory, Error, Parser};
+mod cache;
+mod config;
+mod editor;
+mod files;
+mod hash;
+mod item;
+mod list;
+mod lockfile;
+mod output;
+mod patch;
+mod picker;
+mod readonly;
+mod registry;
+mod restore;
+mod snapshot;
+mod spec;
+mod sysroot;
+mod verify;
+
+use cargo_metadata::{CargoOpt, Metadata, MetadataCommand, Package, PackageId, Target};
+use clap::{error::ErrorKind, CommandFactory, Error, Parser, ValueEnum};
+use spec::{normalize_name, PackageSpec};
 use std::{
-    path::PathBuf,
-    process::{Child, Command},
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    process::ExitCode,
 };
 
 #[derive(Parser)]
@@ -61,59 +271,986 @@ enum Cli {
 /// Open an installed crate in your editor
 #[derive(clap::Args)]
 struct Args {
-    /// The name of the crate to open
-    #[arg(value_name = "CRATE")]
-    package_name: String,
+    /// The crates to open, as names, globs or package id specs (e.g. `syn`, `tokio-*`, `syn@^1`),
+    /// optionally followed by the path of an item within them (e.g. `serde::de::Deserialize`),
+    /// or the paths of workspace members (e.g. `.`)
+    #[arg(
+        value_name = "CRATE",
+        required_unless_present_any = [
+            "list",
+            "packages",
+            "workspace_root",
+            "config_show",
+            "verify",
+        ]
+    )]
+    package_names: Vec<String>,
+
+    /// Crates to open, like the positional arguments
+    #[arg(short, long = "package", value_name = "SPEC", conflicts_with = "list")]
+    packages: Vec<String>,
+
+    /// Open the workspace root directory
+    #[arg(long, conflicts_with_all = ["list", "format", "registry"])]
+    workspace_root: bool,
+
+    #[command(flatten)]
+    metadata: MetadataArgs,
+
+    /// Open crates from the local registry cache rather than the current project's dependencies
+    #[arg(long, conflicts_with_all = ["list", "format"])]
+    registry: bool,
+
+    /// Print the crate's directory (or the item's file) instead of opening an editor
+    #[arg(long, conflicts_with = "format")]
+    print: bool,
+
+    /// Like --print, but terminate the path with a NUL byte instead of a newline
+    #[arg(long, conflicts_with_all = ["print", "format"])]
+    print0: bool,
+
+    /// Print details of the resolved package in the given format instead of opening an editor
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    format: Option<Format>,
+
+    /// List the packages in the dependency graph instead of opening one
+    #[arg(long, conflicts_with = "package_names", help_heading = "List options")]
+    list: bool,
+
+    /// Only list direct dependencies of workspace members
+    #[arg(long, requires = "list", help_heading = "List options")]
+    direct: bool,
+
+    /// Only list packages that aren't direct dependencies of workspace members
+    #[arg(
+        long,
+        requires = "list",
+        conflicts_with = "direct",
+        help_heading = "List options"
+    )]
+    transitive: bool,
+
+    /// Only list packages depended on as this kind of dependency
+    #[arg(
+        long = "kind",
+        value_enum,
+        value_name = "KIND",
+        requires = "list",
+        help_heading = "List options"
+    )]
+    kinds: Vec<list::Kind>,
+
+    /// Only list workspace members
+    #[arg(long, requires = "list", help_heading = "List options")]
+    workspace: bool,
+
+    /// Leave workspace members out of the list
+    #[arg(
+        long,
+        requires = "list",
+        conflicts_with = "workspace",
+        help_heading = "List options"
+    )]
+    no_workspace: bool,
+
+    /// Check that registry crates' sources match the archives they were extracted from,
+    /// for the given crates or every one in the dependency graph
+    #[arg(
+        long,
+        conflicts_with_all = ["list", "registry", "format", "print", "print0", "workspace_root"]
+    )]
+    verify: bool,
+
+    /// Put modified registry crates back as they were extracted from their archives,
+    /// and reset modified git checkouts
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "list",
+            "format",
+            "print",
+            "print0",
+            "workspace_root",
+            "verify",
+        ]
+    )]
+    restore: bool,
+
+    /// With --restore, only list the files that would be restored
+    #[arg(long, requires = "restore")]
+    dry_run: bool,
+
+    /// Stop the editor saving over the crate's files, by making them read-only while it's open,
+    /// or by opening a read-only copy when the editor isn't waited on
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "list",
+            "format",
+            "print",
+            "print0",
+            "workspace_root",
+            "verify",
+            "restore",
+        ]
+    )]
+    read_only: bool,
 
+    /// Copy the crate into the workspace's `patches` directory and patch it in place of the
+    /// original in the workspace's Cargo.toml, then open the copy
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "list",
+            "registry",
+            "format",
+            "print",
+            "print0",
+            "workspace_root",
+            "verify",
+            "restore",
+            "read_only",
+        ]
+    )]
+    patch: bool,
+
+    /// Print the effective configuration and where each value was set, then exit
+    #[arg(long)]
+    config_show: bool,
+
+    /// Report which editor was chosen and where it was configured
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Wait for the editor to exit and exit with its status (default for terminal editors)
+    #[arg(long, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// Return as soon as the editor has been launched
+    #[arg(long)]
+    no_wait: bool,
+
+    /// Open each crate in its own editor, one after the other when waiting,
+    /// rather than passing them all to one editor
+    #[arg(long)]
+    separate: bool,
+}
+
+/// Options forwarded to `cargo metadata`, so the resolved graph matches what's actually built.
+#[derive(clap::Args)]
+struct MetadataArgs {
     /// Use a specific manifest file
-    #[arg(long, value_name = "PATH")]
+    #[arg(long, value_name = "PATH", help_heading = "Manifest options")]
     manifest_path: Option<PathBuf>,
+
+    /// Resolve the dependency graph again rather than using Cargo.lock or the cache
+    #[arg(long, help_heading = "Manifest options")]
+    refresh: bool,
+
+    /// Run without accessing the network
+    #[arg(long, help_heading = "Manifest options")]
+    offline: bool,
+
+    /// Require Cargo.lock and cache are up to date
+    #[arg(long, help_heading = "Manifest options")]
+    frozen: bool,
+
+    /// Require Cargo.lock is up to date
+    #[arg(long, help_heading = "Manifest options")]
+    locked: bool,
+
+    /// Only include dependencies matching the given target triple
+    #[arg(long, value_name = "TRIPLE", help_heading = "Manifest options")]
+    filter_platform: Vec<String>,
+
+    /// Space or comma separated list of features to activate
+    #[arg(
+        short = 'F',
+        long,
+        value_name = "FEATURES",
+        help_heading = "Feature selection"
+    )]
+    features: Vec<String>,
+
+    /// Activate all available features
+    #[arg(long, help_heading = "Feature selection")]
+    all_features: bool,
+
+    /// Do not activate the `default` feature
+    #[arg(long, help_heading = "Feature selection")]
+    no_default_features: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    /// Open the editor, or print a table with --list
+    Human,
+    /// A versioned JSON document describing each package
+    Json,
+}
+
+fn main() -> ExitCode {
+    match try_main() {
+        Ok(code) => code,
+        Err(err) => {
+            let mut command = Cli::command();
+            err.format(&mut command).exit();
+        }
+    }
+}
+
+fn try_main() -> Result<ExitCode, Error> {
+    let Cli::Open(mut args) = Cli::parse();
+
+    let mut config = config::Config::load()?;
+    if args.wait || args.no_wait {
+        config.wait = Some(config::Setting {
+            value: args.wait,
+            source: config::Source::Cli(if args.wait { "--wait" } else { "--no-wait" }),
+        });
+    }
+    if let Some(format) = args.format {
+        config.format = Some(config::Setting {
+            value: format,
+            source: config::Source::Cli("--format"),
+        });
+    }
+    if args.config_show {
+        config.editor = config.editor.or_else(editor::detect_editor);
+        config.show()?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    // A configured format is only a default, so options asking for something else take precedence
+    let overrides_format = args.print
+        || args.print0
+        || args.workspace_root
+        || args.registry
+        || args.verify
+        || args.restore
+        || args.read_only
+        || args.patch;
+    if args.format.is_none() && !overrides_format {
+        args.format = config.format.as_ref().map(|format| format.value);
+    }
+
+    let package_names = args
+        .package_names
+        .iter()
+        .chain(&args.packages)
+        .map(|package_name| config.expand_alias(package_name))
+        // Restoring works on whole crates, so item paths are ignored rather than looked up
+        .map(|package_name| match item::split_item_path(&package_name) {
+            (crate_name, Some(_)) if args.restore => crate_name.to_string(),
+            _ => package_name,
+        });
+
+    // The standard library isn't in the dependency graph, so it's opened from the toolchain's sources
+    let (std_names, package_names): (Vec<String>, Vec<String>) =
+        if args.format != Some(Format::Json) && !args.verify && !args.patch {
+            package_names.partition(|package_name| is_std_crate(package_name))
+        } else {
+            (Vec::new(), package_names.collect())
+        };
+    let mut targets = get_std_targets(&std_names, args.metadata.manifest_path.as_deref())?;
+    if !std_names.is_empty() && package_names.is_empty() && !args.workspace_root {
+        return open_targets(&args, &config, &targets);
+    }
+
+    if args.registry {
+        targets.extend(get_registry_targets(&package_names)?);
+        return open_targets(&args, &config, &targets);
+    }
+
+    if let Some(cached) = get_cached_targets(&args, &package_names) {
+        targets.extend(cached);
+        return open_targets(&args, &config, &targets);
+    }
+
+    let metadata = get_metadata(&args.metadata)?;
+    cache::store(&args.metadata, &metadata);
+
+    if args.list {
+        return list_packages(&args, &metadata);
+    }
+
+    if args.verify {
+        return verify_packages(&package_names, &metadata);
+    }
+
+    if args.workspace_root {
+        targets.push(editor::Target {
+            path: metadata.workspace_root.clone().into_std_path_buf(),
+            position: None,
+        });
+    }
+
+    let resolved = resolve_packages(&package_names, &metadata)?;
+
+    if args.patch {
+        let patched = resolved
+            .iter()
+            .map(|resolved| {
+                let dir = get_package_path(resolved.package)?;
+                let workspace_root = metadata.workspace_root.as_std_path();
+                let copy = patch::patch_package(resolved.package, &dir, workspace_root)?;
+                let location = resolved.location.as_ref().map(|location| item::Location {
+                    file: copy.join(location.file.strip_prefix(&dir).unwrap_or(&location.file)),
+                    position: location.position,
+                });
+                Ok(package_target(copy, location))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        return open_targets(&args, &config, &patched);
+    }
+
+    if args.format == Some(Format::Json) {
+        let packages: Vec<_> = resolved
+            .iter()
+            .map(|resolved| {
+                let item = resolved.item_path.zip(resolved.location.as_ref());
+                output::PackageOutput::new(resolved.package, item)
+            })
+            .collect();
+        output::print_json(&packages)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let resolved = resolved
+        .into_iter()
+        .map(|resolved| {
+            let dir = get_package_path(resolved.package)?;
+            Ok(package_target(dir, resolved.location))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    targets.extend(resolved);
+
+    open_targets(&args, &config, &targets)
+}
+
+fn list_packages(args: &Args, metadata: &Metadata) -> Result<ExitCode, Error> {
+    let filter = list::Filter {
+        direct: args.direct,
+        transitive: args.transitive,
+        kinds: args.kinds.clone(),
+        workspace: args.workspace,
+        no_workspace: args.no_workspace,
+    };
+    let packages = list::list_packages(metadata, &filter);
+
+    match args.format {
+        Some(Format::Json) => {
+            let packages: Vec<_> = packages
+                .into_iter()
+                .map(|package| output::PackageOutput::new(package, None))
+                .collect();
+            output::print_json(&packages)?;
+        }
+        Some(Format::Human) | None => output::print_table(&packages)?,
+    }
+
+    Ok(ExitCode::SUCCESS)
 }
 
-fn main() {
-    if let Err(err) = try_main() {
-        let mut command = Cli::command();
-        err.format(&mut command).exit();
+/// Verifies the named packages, or every registry package in the graph if none are named.
+fn verify_packages(package_names: &[String], metadata: &Metadata) -> Result<ExitCode, Error> {
+    let packages: Vec<&Package> = if package_names.is_empty() {
+        let mut packages: Vec<&Package> = metadata
+            .packages
+            .iter()
+            .filter(|package| verify::is_verifiable(package))
+            .collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name).then(a.version.cmp(&b.version)));
+        packages
+    } else {
+        resolve_packages(package_names, metadata)?
+            .into_iter()
+            .map(|resolved| resolved.package)
+            .collect()
+    };
+
+    if verify::verify_packages(&packages)? {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Prints the targets, restores them, or opens them in the editor, as asked.
+fn open_targets(
+    args: &Args,
+    config: &config::Config,
+    targets: &[editor::Target],
+) -> Result<ExitCode, Error> {
+    if args.restore {
+        let paths: Vec<&Path> = targets.iter().map(|target| target.path.as_path()).collect();
+        restore::restore_paths(&paths, args.dry_run)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.print || args.print0 {
+        for target in targets {
+            output::print_path(&target.path, if args.print0 { b'\0' } else { b'\n' })?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let editor = editor::get_editor(config, args.verbose)?;
+    let wait = match &config.wait {
+        Some(wait) => wait.value,
+        None => editor.waits_by_default(),
+    };
+
+    // Permissions can only be given back once the editor has closed, so otherwise open a copy
+    let copies;
+    let targets = if args.read_only && !wait {
+        copies = readonly::copy_targets(targets)?;
+        &copies[..]
+    } else {
+        targets
+    };
+
+    let groups: Vec<&[editor::Target]> = if args.separate {
+        targets.chunks(1).collect()
+    } else {
+        vec![targets]
+    };
+
+    let mut code = ExitCode::SUCCESS;
+    for targets in groups {
+        // Installed crates can only be checked for edits once the editor has closed
+        let snapshots = if wait {
+            snapshot::Snapshot::take_all(targets)
+        } else {
+            Vec::new()
+        };
+
+        let protection = if args.read_only && wait {
+            Some(readonly::protect(targets)?)
+        } else {
+            None
+        };
+
+        let child = editor::run_editor(&editor, targets)?;
+        if wait {
+            let status = editor::wait_editor(child)?;
+            if !status.success() {
+                code = editor::exit_code(status);
+            }
+        }
+        drop(protection);
+
+        for snapshot in &snapshots {
+            snapshot.warn_changes();
+        }
     }
+
+    Ok(code)
 }
 
-fn try_main() -> Result<(), Error> {
-    let Cli::Open(args) = Cli::parse();
+/// Resolves plain crate names and specs straight from `Cargo.lock`, or from the cache left by
+/// a previous run, skipping `cargo metadata`. Returns `None` if anything needs the full
+/// dependency graph, leaving it to the slower path.
+fn get_cached_targets(args: &Args, package_names: &[String]) -> Option<Vec<editor::Target>> {
+    if args.list
+        || args.format == Some(Format::Json)
+        || args.workspace_root
+        || args.verify
+        || args.patch
+        || args.metadata.refresh
+    {
+        return None;
+    }
+
+    let manifest_path = args.metadata.manifest_path.as_deref();
+    let mut cache = None;
+
+    let mut targets: Vec<editor::Target> = package_names
+        .iter()
+        .map(|package_name| {
+            if package_name.contains("::") || is_member_path(package_name) {
+                return None;
+            }
+
+            let spec = PackageSpec::parse(package_name).ok()?;
+            if spec.is_glob() {
+                return None;
+            }
+
+            let path = lockfile::find_package_dir(manifest_path, &spec).or_else(|| {
+                cache
+                    .get_or_insert_with(|| cache::Cache::load(&args.metadata))
+                    .as_ref()?
+                    .find_package_dir(&spec)
+            })?;
+
+            Some(editor::Target {
+                path,
+                position: None,
+            })
+        })
+        .collect::<Option<_>>()?;
+
+    // Specs naming the same package, like `serde` and `serde@1`, open it once
+    let mut seen = HashSet::new();
+    targets.retain(|target| seen.insert(target.path.clone()));
+    Some(targets)
+}
 
-    let metadata = get_metadata(args.manifest_path)?;
-    let package = get_package(&args.package_name, &metadata)?;
-    let package_path = get_package_path(package)?;
-    let editor_path = get_editor_path()?;
+/// Whether `package_name`, ignoring any item path, names one of the crates shipped with the toolchain.
+fn is_std_crate(package_name: &str) -> bool {
+    let (crate_name, _) = item::split_item_path(package_name);
+    sysroot::STD_CRATES.contains(&crate_name)
+}
 
-    run_editor(editor_path, package_path)?;
+/// Resolves standard library crates, and items within them, to the toolchain's `rust-src` sources.
+fn get_std_targets(
+    package_names: &[String],
+    manifest_path: Option<&Path>,
+) -> Result<Vec<editor::Target>, Error> {
+    package_names
+        .iter()
+        .map(|package_name| {
+            let (crate_name, item_path) = item::split_item_path(package_name);
+            let dir = sysroot::find_std_crate(crate_name, manifest_path)?;
+            let location = item_path
+                .map(|item_path| item::find_item(&dir.join("src").join("lib.rs"), item_path))
+                .transpose()?;
+            Ok(package_target(dir, location))
+        })
+        .collect()
+}
 
-    Ok(())
+/// Resolves each name to the newest matching crate in the local registry cache, unpacking it if needed.
+fn get_registry_targets(package_names: &[String]) -> Result<Vec<editor::Target>, Error> {
+    package_names
+        .iter()
+        .map(|package_name| {
+            let (package_name, item_path) = item::split_item_path(package_name);
+            let spec = PackageSpec::parse(package_name)?;
+            let krate = registry::find_crate(&spec)?;
+            let dir = registry::extract(&krate)?;
+            let location = item_path
+                .map(|item_path| item::find_item(&registry::lib_path(&dir), item_path))
+                .transpose()?;
+            Ok(package_target(dir, location))
+        })
+        .collect()
 }
 
-fn get_metadata(manifest_path: Option<PathBuf>) -> Result<Metadata, Error> {
+/// A package named on the command line, with the item looked up within it if a path was given.
+struct Resolved<'a> {
+    package: &'a Package,
+    item_path: Option<&'a str>,
+    location: Option<item::Location>,
+}
+
+/// Resolves each name to a package, or to every package matching it if it's a glob.
+fn resolve_packages<'a>(
+    package_names: &'a [String],
+    metadata: &'a Metadata,
+) -> Result<Vec<Resolved<'a>>, Error> {
+    let mut resolved: Vec<Resolved> = Vec::new();
+
+    for package_name in package_names {
+        let (package_name, item_path) = item::split_item_path(package_name);
+        if is_member_path(package_name) {
+            let package = get_member_by_path(Path::new(package_name), metadata)?;
+            let location = item_path
+                .map(|item_path| get_item_location(package, item_path))
+                .transpose()?;
+            resolved.push(Resolved {
+                package,
+                item_path,
+                location,
+            });
+            continue;
+        }
+
+        let spec = PackageSpec::parse(package_name)?;
+
+        let packages = if spec.is_glob() {
+            let mut packages: Vec<&Package> = metadata
+                .packages
+                .iter()
+                .filter(|package| spec.matches(package))
+                .collect();
+            if packages.is_empty() {
+                return Err(Error::raw(
+                    ErrorKind::InvalidValue,
+                    format!("No packages match: {}", spec.name),
+                ));
+            }
+            packages.sort_by(|a, b| a.name.cmp(&b.name).then(a.version.cmp(&b.version)));
+            packages
+        } else {
+            vec![get_package(&spec, metadata)?]
+        };
+
+        for package in packages {
+            let is_duplicate = resolved
+                .iter()
+                .any(|other| other.package.id == package.id && other.item_path == item_path);
+            if is_duplicate {
+                continue;
+            }
+
+            let location = item_path
+                .map(|item_path| get_item_location(package, item_path))
+                .transpose()?;
+            resolved.push(Resolved {
+                package,
+                item_path,
+                location,
+            });
+        }
+    }
+
+    Ok(resolved)
+}
+
+fn get_metadata(args: &MetadataArgs) -> Result<Metadata, Error> {
     let mut cmd = MetadataCommand::new();
-    if let Some(manifest_path) = manifest_path {
+    if let Some(manifest_path) = &args.manifest_path {
         cmd.manifest_path(manifest_path);
     }
 
+    if !args.features.is_empty() {
+        cmd.features(CargoOpt::SomeFeatures(args.features.clone()));
+    }
+    if args.all_features {
+        cmd.features(CargoOpt::AllFeatures);
+    }
+    if args.no_default_features {
+        cmd.features(CargoOpt::NoDefaultFeatures);
+    }
+
+    let mut options = Vec::new();
+    for (flag, enabled) in [
+        ("--offline", args.offline),
+        ("--frozen", args.frozen),
+        ("--locked", args.locked),
+    ] {
+        if enabled {
+            options.push(flag.to_string());
+        }
+    }
+    for triple in &args.filter_platform {
+        options.extend(["--filter-platform".to_string(), triple.clone()]);
+    }
+    cmd.other_options(options);
+
     cmd.exec()
         .map_err(|e| Error::raw(ErrorKind::Io, format!("Metadata error: {}", e)))
 }
 
-fn get_package<'a>(
-    package_name: &'a str,
-    metadata: &'a Metadata,
-) -> Result<&'a Package, Error> {
-    metadata
+fn get_package<'a>(spec: &PackageSpec, metadata: &'a Metadata) -> Result<&'a Package, Error> {
+    let mut candidates: Vec<&Package> = metadata
         .packages
         .iter()
-        .find(|package| package.name == package_name)
+        .filter(|package| spec.matches(package))
+        .collect();
+    if candidates
+        .iter()
+        .any(|package| metadata.workspace_members.contains(&package.id))
+    {
+        candidates.retain(|package| metadata.workspace_members.contains(&package.id));
+    }
+    if candidates.iter().any(|package| spec.matches_exactly(package)) {
+        candidates.retain(|package| spec.matches_exactly(package));
+    }
+    if candidates.is_empty() {
+        candidates = get_aliased_packages(spec, metadata);
+    }
+    candidates.sort_by(|a, b| a.version.cmp(&b.version));
+
+    let suggestions = similar_names(&spec.name, metadata);
+    let err = match candidates.len() {
+        0 if suggestions.is_empty() => Error::raw(
+            ErrorKind::InvalidValue,
+            format!("Package not found: {}", spec.name),
+        ),
+        0 => Error::raw(
+            ErrorKind::InvalidValue,
+            format!(
+                "Package not found: {}\n\n  did you mean: {}?",
+                spec.name,
+                suggestions.join(", ")
+            ),
+        ),
+        1 => return Ok(candidates[0]),
+        _ => {
+            let candidates: Vec<String> = candidates
+                .iter()
+                .map(|package| {
+                    let source = source_name(package);
+                    format!("  {}@{} ({})", package.name, package.version, source)
+                })
+                .collect();
+
+            Error::raw(
+                ErrorKind::InvalidValue,
+                format!(
+                    "Package {} is ambiguous, specify one of:\n{}",
+                    spec.name,
+                    candidates.join("\n")
+                ),
+            )
+        }
+    };
+
+    if !picker::is_interactive() {
+        return Err(err);
+    }
+
+    let prompt = if candidates.is_empty() {
+        // Closest names first, then everything else alphabetically
+        let rank = |package: &Package| {
+            let position = suggestions.iter().position(|name| *name == package.name);
+            position.unwrap_or(suggestions.len())
+        };
+
+        candidates = metadata.packages.iter().collect();
+        candidates.sort_by(|a, b| {
+            rank(a)
+                .cmp(&rank(b))
+                .then(a.name.cmp(&b.name))
+                .then(a.version.cmp(&b.version))
+        });
+        format!("Package {} not found, pick one:", spec.name)
+    } else {
+        format!("Package {} is ambiguous, pick one:", spec.name)
+    };
+
+    pick_package(&prompt, candidates, metadata)?.ok_or(err)
+}
+
+/// Whether `package_name` is the path of a workspace member rather than a spec, like `.` or `./crates/api`.
+fn is_member_path(package_name: &str) -> bool {
+    package_name == "."
+        || package_name == ".."
+        || ["./", "../", "/"]
+            .iter()
+            .any(|prefix| package_name.starts_with(prefix))
+}
+
+/// Finds the workspace member whose directory contains `path`, the innermost if they're nested.
+fn get_member_by_path<'a>(path: &Path, metadata: &'a Metadata) -> Result<&'a Package, Error> {
+    let path = std::fs::canonicalize(path).map_err(|e| {
+        Error::raw(
+            ErrorKind::Io,
+            format!("Cannot open {}: {}", path.display(), e),
+        )
+    })?;
+
+    metadata
+        .workspace_packages()
+        .into_iter()
+        .filter(|package| {
+            package
+                .manifest_path
+                .parent()
+                .is_some_and(|dir| path.starts_with(dir))
+        })
+        .max_by_key(|package| package.manifest_path.as_str().len())
         .ok_or_else(|| {
             Error::raw(
                 ErrorKind::InvalidValue,
-                format!("Package not found: {}", package_name),
+                format!("No workspace member found at {}", path.display()),
+            )
+        })
+}
+
+/// Finds packages known by another name in code: first dependencies renamed in the current
+/// workspace member's manifest, like `tokio1 = { package = "tokio" }`, then packages whose
+/// library target is named differently, like `crypto` for `rust-crypto`.
+fn get_aliased_packages<'a>(spec: &PackageSpec, metadata: &'a Metadata) -> Vec<&'a Package> {
+    let crate_name = spec.name.replace('-', "_");
+    let packages: HashMap<&PackageId, &Package> = metadata
+        .packages
+        .iter()
+        .map(|package| (&package.id, package))
+        .collect();
+
+    // The resolve graph names each dependency edge after the crate name used in code,
+    // which accounts for both renames and library target names
+    let members = current_members(metadata);
+    let mut renamed: Vec<&Package> = metadata
+        .resolve
+        .iter()
+        .flat_map(|resolve| &resolve.nodes)
+        .filter(|node| members.contains(&&node.id))
+        .flat_map(|node| &node.deps)
+        .filter(|dep| dep.name == crate_name)
+        .filter_map(|dep| packages.get(&dep.pkg).copied())
+        .filter(|package| spec.matches_version_and_source(package))
+        .collect();
+
+    if !renamed.is_empty() {
+        renamed.sort_by(|a, b| a.id.cmp(&b.id));
+        renamed.dedup_by(|a, b| a.id == b.id);
+        return renamed;
+    }
+
+    metadata
+        .packages
+        .iter()
+        .filter(|package| {
+            package
+                .targets
+                .iter()
+                .any(|target| target.is_lib() && target.name == crate_name)
+        })
+        .filter(|package| spec.matches_version_and_source(package))
+        .collect()
+}
+
+/// The workspace member for the manifest or directory cargo-open was run from,
+/// or every workspace member when that can't be narrowed down.
+fn current_members(metadata: &Metadata) -> Vec<&PackageId> {
+    if let Some(root) = metadata.root_package() {
+        return vec![&root.id];
+    }
+
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let current = metadata
+        .workspace_packages()
+        .into_iter()
+        .filter(|package| {
+            package
+                .manifest_path
+                .parent()
+                .is_some_and(|dir| current_dir.starts_with(dir))
+        })
+        .max_by_key(|package| package.manifest_path.as_str().len());
+
+    match current {
+        Some(package) => vec![&package.id],
+        None => metadata.workspace_members.iter().collect(),
+    }
+}
+
+/// Package names close to `name`, nearest first, for suggesting when it isn't found.
+fn similar_names<'a>(name: &str, metadata: &'a Metadata) -> Vec<&'a str> {
+    const MAX_SUGGESTIONS: usize = 5;
+
+    let name = normalize_name(name);
+    let max_distance = (name.len() / 3).max(1);
+
+    let mut names: Vec<(usize, &str)> = metadata
+        .packages
+        .iter()
+        .map(|package| {
+            let distance = strsim::levenshtein(&name, &normalize_name(&package.name));
+            (distance, package.name.as_str())
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+
+    names.sort();
+    names.dedup_by_key(|(_, name)| *name);
+    names.truncate(MAX_SUGGESTIONS);
+    names.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Asks the user to pick one of `candidates`, listed as aligned rows of
+/// name, version, source kind and the workspace members that depend on it.
+fn pick_package<'a>(
+    prompt: &str,
+    candidates: Vec<&'a Package>,
+    metadata: &Metadata,
+) -> Result<Option<&'a Package>, Error> {
+    let dependents = workspace_dependents(metadata);
+    let name_width = candidates.iter().map(|p| p.name.len()).max().unwrap_or(0);
+    let version_width = candidates
+        .iter()
+        .map(|p| p.version.to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    let items: Vec<String> = candidates
+        .iter()
+        .map(|package| {
+            let used_by = match dependents.get(&package.id) {
+                Some(members) => format!("used by {}", members.join(", ")),
+                None => String::new(),
+            };
+            format!(
+                "{:name_width$}  {:version_width$}  {:8}  {}",
+                package.name,
+                package.version.to_string(),
+                source_kind(package),
+                used_by,
             )
         })
+        .collect();
+
+    let selected = picker::pick(prompt, &items)?;
+    Ok(selected.map(|index| candidates[index]))
+}
+
+/// Maps each package to the names of the workspace members that depend on it, directly or transitively.
+fn workspace_dependents(metadata: &Metadata) -> HashMap<&PackageId, Vec<&str>> {
+    let mut dependents: HashMap<&PackageId, Vec<&str>> = HashMap::new();
+    let Some(resolve) = &metadata.resolve else {
+        return dependents;
+    };
+
+    let nodes: HashMap<&PackageId, _> = resolve
+        .nodes
+        .iter()
+        .map(|node| (&node.id, node))
+        .collect();
+
+    for member in metadata.workspace_packages() {
+        let mut seen = HashSet::new();
+        let mut stack = vec![&member.id];
+
+        while let Some(id) = stack.pop() {
+            let Some(node) = nodes.get(id) else {
+                continue;
+            };
+            for dep in &node.deps {
+                if seen.insert(&dep.pkg) {
+                    stack.push(&dep.pkg);
+                }
+            }
+        }
+
+        for id in seen {
+            dependents.entry(id).or_default().push(&member.name);
+        }
+    }
+
+    dependents
+}
+
+/// Whether a package comes from a registry, a git repository, a local path, or a directory
+/// of vendored sources replacing its registry, which `cargo vendor` marks with a checksum file.
+fn source_kind(package: &Package) -> &'static str {
+    let is_vendored = || {
+        package
+            .manifest_path
+            .with_file_name(".cargo-checksum.json")
+            .exists()
+    };
+
+    match &package.source {
+        Some(source) if source.repr.starts_with("git+") => "git",
+        Some(_) if is_vendored() => "vendored",
+        Some(_) => "registry",
+        None => "path",
+    }
+}
+
+/// A short description of where a package comes from, for showing to the user.
+fn source_name(package: &Package) -> String {
+    match &package.source {
+        Some(source) => source.repr.clone(),
+        None => package
+            .manifest_path
+            .parent()
+            .map_or_else(|| package.manifest_path.to_string(), |path| path.to_string()),
+    }
 }
 
 fn get_package_path(package: &Package) -> Result<PathBuf, Error> {
@@ -124,22 +1261,38 @@ fn get_package_path(package: &Package) -> Result<PathBuf, Error> {
         .ok_or_else(|| Error::raw(ErrorKind::Io, "Path error"))
 }
 
-fn get_editor_path() -> Result<PathBuf, Error> {
-    std::env::var("CARGO_EDITOR")
-        .or_else(|_| std::env::var("VISUAL"))
-        .or_else(|_| std::env::var("EDITOR"))
-        .map_err(|_| Error::raw(ErrorKind::Io, "Cannot resolve editor"))
-        .map(PathBuf::from)
+/// The package's library target, of whichever crate type.
+fn lib_target(package: &Package) -> Option<&Target> {
+    package.targets.iter().find(|target| {
+        target
+            .kind
+            .iter()
+            .any(|kind| kind.ends_with("lib") || kind == "proc-macro")
+    })
 }
 
-fn run_editor(editor_path: PathBuf, package_path: PathBuf) -> Result<Child, Error> {
-    let mut cmd = Command::new(editor_path.clone());
-    cmd.arg(package_path);
-
-    cmd.spawn().map_err(|_| {
+/// Finds the file and position defining `item_path` within the package's library.
+fn get_item_location(package: &Package, item_path: &str) -> Result<item::Location, Error> {
+    let lib = lib_target(package).ok_or_else(|| {
         Error::raw(
-            ErrorKind::Io,
-            format!("Cannot execute editor: {}", editor_path.display()),
+            ErrorKind::InvalidValue,
+            format!("Package {} has no library to find items in", package.name),
         )
-    })
+    })?;
+
+    item::find_item(lib.src_path.as_std_path(), item_path)
+}
+
+/// Opens the item's definition if one was looked up, otherwise the package's directory.
+fn package_target(dir: PathBuf, location: Option<item::Location>) -> editor::Target {
+    match location {
+        Some(location) => editor::Target {
+            path: location.file,
+            position: Some(location.position),
+        },
+        None => editor::Target {
+            path: dir,
+            position: None,
+        },
+    }
 }
@@ -0,0 +1,152 @@
+//! Output describing resolved packages, as a table or machine-readable JSON.
+
+use crate::{item::Location, lib_target, source_kind};
+use cargo_metadata::Package;
+use clap::{error::ErrorKind, Error};
+use serde::Serialize;
+use std::{
+    fmt::Write as _,
+    io::{self, Write as _},
+    path::Path,
+};
+
+/// Bumped whenever a field is removed or changes meaning. Adding fields doesn't change the version.
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Output<'a> {
+    schema_version: u32,
+    packages: &'a [PackageOutput<'a>],
+}
+
+/// Everything cargo-open knows about a resolved package.
+#[derive(Serialize)]
+pub struct PackageOutput<'a> {
+    name: &'a str,
+    version: String,
+    id: &'a str,
+    /// One of `registry`, `git`, `path` or `vendored`.
+    source_kind: &'static str,
+    /// The source as reported by cargo, or `null` for path dependencies and workspace members.
+    source: Option<&'a str>,
+    manifest_path: &'a Path,
+    root: &'a Path,
+    lib_path: Option<&'a Path>,
+    edition: &'static str,
+    license: Option<&'a str>,
+    repository: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item: Option<ItemOutput<'a>>,
+}
+
+/// The item looked up within the package, when a path was given.
+#[derive(Serialize)]
+struct ItemOutput<'a> {
+    /// The item's path within the crate, without the crate name.
+    path: &'a str,
+    file: &'a Path,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> PackageOutput<'a> {
+    pub fn new(package: &'a Package, item: Option<(&'a str, &'a Location)>) -> Self {
+        let manifest_path = package.manifest_path.as_std_path();
+        let lib = lib_target(package);
+
+        PackageOutput {
+            name: &package.name,
+            version: package.version.to_string(),
+            id: &package.id.repr,
+            source_kind: source_kind(package),
+            source: package.source.as_ref().map(|source| source.repr.as_str()),
+            manifest_path,
+            root: manifest_path.parent().unwrap_or(manifest_path),
+            lib_path: lib.map(|lib| lib.src_path.as_std_path()),
+            edition: package.edition.as_str(),
+            license: package.license.as_deref(),
+            repository: package.repository.as_deref(),
+            item: item.map(|(path, location)| ItemOutput {
+                path,
+                file: &location.file,
+                line: location.position.line,
+                column: location.position.column,
+            }),
+        }
+    }
+}
+
+pub fn print_json(packages: &[PackageOutput]) -> Result<(), Error> {
+    let output = Output {
+        schema_version: SCHEMA_VERSION,
+        packages,
+    };
+
+    let json = serde_json::to_string_pretty(&output)
+        .map_err(|e| Error::raw(ErrorKind::Io, format!("Cannot serialize output: {}", e)))?;
+
+    write_stdout(&[json.as_bytes(), b"\n"])
+}
+
+/// Writes `path` to stdout as is, so paths that aren't valid UTF-8 survive being piped to other tools.
+pub fn print_path(path: &Path, terminator: u8) -> Result<(), Error> {
+    write_stdout(&[path.as_os_str().as_encoded_bytes(), &[terminator]])
+}
+
+/// Prints an aligned table of each package's name, version, source kind and directory.
+pub fn print_table(packages: &[&Package]) -> Result<(), Error> {
+    let rows: Vec<[String; 4]> = packages
+        .iter()
+        .map(|package| {
+            let dir = package.manifest_path.parent().unwrap_or(&package.manifest_path);
+            [
+                package.name.clone(),
+                package.version.to_string(),
+                source_kind(package).to_string(),
+                dir.to_string(),
+            ]
+        })
+        .collect();
+
+    let header = ["NAME", "VERSION", "SOURCE", "DIRECTORY"].map(String::from);
+    let mut widths = header.clone().map(|column| column.len());
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.len());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let _ = writeln!(
+            table,
+            "{:name$}  {:version$}  {:source$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            name = widths[0],
+            version = widths[1],
+            source = widths[2],
+        );
+    }
+
+    write_stdout(&[table.as_bytes()])
+}
+
+/// Writes to stdout, treating a closed pipe as success so output can be cut short with `head` and the like.
+pub fn write_stdout(parts: &[&[u8]]) -> Result<(), Error> {
+    let mut stdout = io::stdout().lock();
+    let result = parts
+        .iter()
+        .try_for_each(|part| stdout.write_all(part))
+        .and_then(|_| stdout.flush());
+
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(Error::raw(
+            ErrorKind::Io,
+            format!("Cannot write output: {}", e),
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,254 @@
+//! Vendoring dependencies into the workspace, patched in place of the originals for editing.
+
+use crate::files::{self, CopyOptions};
+use cargo_metadata::{semver::Version, Package};
+use clap::{error::ErrorKind, Error};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::{DocumentMut, InlineTable, Item, Table, Value};
+
+/// Where copies go, relative to the workspace root.
+const PATCHES_DIR: &str = "patches";
+
+/// Build output, and the marker cargo leaves in its own copies of a crate, which mean nothing
+/// in a patched one.
+const SKIPPED_PATHS: &[&str] = &["target", ".cargo-ok"];
+
+/// Copies the package from `dir` into `patches/<name>-<version>` under the workspace root, unless
+/// it's been copied already, and adds a `[patch]` entry for the copy to the workspace's
+/// `Cargo.toml`. Returns the copy's directory.
+pub fn patch_package(
+    package: &Package,
+    dir: &Path,
+    workspace_root: &Path,
+) -> Result<PathBuf, Error> {
+    let relative = format!("{}/{}-{}", PATCHES_DIR, package.name, package.version);
+    let dest = workspace_root.join(&relative);
+
+    let Some(source) = &package.source else {
+        // Patching again resolves to the copy, so open it again too
+        if dir == dest {
+            return Ok(dest);
+        }
+        return Err(Error::raw(
+            ErrorKind::InvalidValue,
+            format!(
+                "Package {} is local already, so can be edited where it is",
+                package.name
+            ),
+        ));
+    };
+
+    if !dest.exists() {
+        let options = CopyOptions {
+            skip: SKIPPED_PATHS,
+            read_only: false,
+        };
+        files::copy_dir(dir, &dest, &options).map_err(|e| {
+            Error::raw(
+                ErrorKind::Io,
+                format!("Cannot copy {} to {}: {}", dir.display(), dest.display(), e),
+            )
+        })?;
+        eprintln!("Copied {} {} to {}", package.name, package.version, dest.display());
+    }
+
+    let manifest_path = workspace_root.join("Cargo.toml");
+    let manifest = fs::read_to_string(&manifest_path).map_err(|e| {
+        Error::raw(
+            ErrorKind::Io,
+            format!("Cannot read {}: {}", manifest_path.display(), e),
+        )
+    })?;
+    let mut document: DocumentMut = manifest.parse().map_err(|e| {
+        Error::raw(
+            ErrorKind::InvalidValue,
+            format!("Cannot parse {}: {}", manifest_path.display(), e),
+        )
+    })?;
+
+    let source_key = patch_source(&source.repr);
+    if add_patch(&mut document, &source_key, &package.name, &package.version, &relative)? {
+        fs::write(&manifest_path, document.to_string()).map_err(|e| {
+            Error::raw(
+                ErrorKind::Io,
+                format!("Cannot write {}: {}", manifest_path.display(), e),
+            )
+        })?;
+        eprintln!(
+            "Patched {} with {} in {}",
+            package.name,
+            relative,
+            manifest_path.display()
+        );
+    }
+
+    Ok(dest)
+}
+
+/// The key under `[patch]` for a package source: `crates-io`, or the registry or repository URL.
+fn patch_source(repr: &str) -> String {
+    if let Some(url) = repr.strip_prefix("git+") {
+        // Git sources carry the branch, tag or rev as a query, and the locked commit as a fragment
+        let end = url.find(['?', '#']).unwrap_or(url.len());
+        return url[..end].to_string();
+    }
+
+    const CRATES_IO: &[&str] = &[
+        "registry+https://github.com/rust-lang/crates.io-index",
+        "sparse+https://index.crates.io/",
+    ];
+    if CRATES_IO.contains(&repr) {
+        return "crates-io".to_string();
+    }
+    repr.strip_prefix("registry+").unwrap_or(repr).to_string()
+}
+
+/// Adds `[patch.<source>] <name> = { path = "<path>" }`, leaving the rest of the document as is.
+/// If the name is already patched with something else, as when two versions of a crate are used,
+/// the entry is keyed by name and version instead. Returns whether the document changed.
+fn add_patch(
+    document: &mut DocumentMut,
+    source: &str,
+    name: &str,
+    version: &Version,
+    path: &str,
+) -> Result<bool, Error> {
+    let not_a_table = |key: &str| {
+        Error::raw(
+            ErrorKind::InvalidValue,
+            format!("{} in the workspace's Cargo.toml isn't a table", key),
+        )
+    };
+
+    let patch = document
+        .entry("patch")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_like_mut()
+        .ok_or_else(|| not_a_table("[patch]"))?;
+    let entries = patch
+        .entry(source)
+        .or_insert(Item::Table(Table::new()))
+        .as_table_like_mut()
+        .ok_or_else(|| not_a_table(&format!("[patch.\"{}\"]", source)))?;
+
+    // Keys are dependency names, which can't contain the dots in a version
+    let versioned = format!("{}-{}", name, version).replace(['.', '+'], "_");
+    let keys = [name.to_string(), versioned];
+    let key = keys.iter().find(|key| {
+        entries
+            .get(key)
+            .is_none_or(|entry| entry.get("path").and_then(Item::as_str) == Some(path))
+    });
+    let Some(key) = key else {
+        return Err(Error::raw(
+            ErrorKind::InvalidValue,
+            format!(
+                "{} from {} is already patched in the workspace's Cargo.toml",
+                name, source
+            ),
+        ));
+    };
+    if entries.contains_key(key) {
+        return Ok(false);
+    }
+
+    let mut entry = InlineTable::new();
+    entry.insert("path", Value::from(path));
+    if key != name {
+        entry.insert("package", Value::from(name));
+    }
+    entries.insert(key, Item::Value(Value::InlineTable(entry)));
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(manifest: &str, name: &str, version: &str, path: &str) -> Result<String, Error> {
+        let mut document: DocumentMut = manifest.parse().unwrap();
+        let version = Version::parse(version).unwrap();
+        add_patch(&mut document, "crates-io", name, &version, path)?;
+        Ok(document.to_string())
+    }
+
+    #[test]
+    fn finds_patch_sources() {
+        let crates_io = [
+            "registry+https://github.com/rust-lang/crates.io-index",
+            "sparse+https://index.crates.io/",
+        ];
+        for repr in crates_io {
+            assert_eq!(patch_source(repr), "crates-io");
+        }
+
+        let registry = "registry+https://example.com/index";
+        assert_eq!(patch_source(registry), "https://example.com/index");
+        let sparse = "sparse+https://example.com/index/";
+        assert_eq!(patch_source(sparse), sparse);
+
+        let git = "git+https://github.com/dtolnay/syn?branch=master#0123456789abcdef";
+        assert_eq!(patch_source(git), "https://github.com/dtolnay/syn");
+        let git = "git+https://github.com/dtolnay/syn#0123456789abcdef";
+        assert_eq!(patch_source(git), "https://github.com/dtolnay/syn");
+    }
+
+    #[test]
+    fn adds_a_patch_table() {
+        let manifest = "[package]\nname = \"app\" # the app\n\n[dependencies]\nsyn = \"2\"\n";
+        let patched = patch(manifest, "syn", "2.0.1", "patches/syn-2.0.1").unwrap();
+        assert_eq!(
+            patched,
+            format!(
+                "{}\n[patch.crates-io]\nsyn = {{ path = \"patches/syn-2.0.1\" }}\n",
+                manifest
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_existing_entries_and_comments() {
+        let manifest = "[patch.crates-io]\n# local fork\nserde = { path = \"../serde\" } # wip\n\n\
+                        [profile.dev]\nopt-level = 1\n";
+        let patched = patch(manifest, "syn", "2.0.1", "patches/syn-2.0.1").unwrap();
+        assert_eq!(
+            patched,
+            "[patch.crates-io]\n# local fork\nserde = { path = \"../serde\" } # wip\n\
+             syn = { path = \"patches/syn-2.0.1\" }\n\n[profile.dev]\nopt-level = 1\n"
+        );
+    }
+
+    #[test]
+    fn keys_other_versions_by_version() {
+        let manifest = "[patch.crates-io]\nsyn = { path = \"patches/syn-1.0.109\" }\n";
+        let patched = patch(manifest, "syn", "2.0.1-rc.1", "patches/syn-2.0.1-rc.1").unwrap();
+        assert!(patched.ends_with(
+            "syn-2_0_1-rc_1 = { path = \"patches/syn-2.0.1-rc.1\", package = \"syn\" }\n"
+        ));
+    }
+
+    #[test]
+    fn leaves_existing_patches_alone() {
+        let manifest = "[patch.crates-io]\nsyn = { path = \"patches/syn-2.0.1\" }\n";
+        let mut document: DocumentMut = manifest.parse().unwrap();
+        let version = Version::parse("2.0.1").unwrap();
+        let changed =
+            add_patch(&mut document, "crates-io", "syn", &version, "patches/syn-2.0.1").unwrap();
+        assert!(!changed);
+    }
+
+    #[test]
+    fn refuses_when_both_keys_are_taken() {
+        let manifest = "[patch.crates-io]\nsyn = { path = \"a\" }\nsyn-2_0_1 = { path = \"b\" }\n";
+        assert!(patch(manifest, "syn", "2.0.1", "patches/syn-2.0.1").is_err());
+        assert!(patch("patch = 1\n", "syn", "2.0.1", "patches/syn-2.0.1").is_err());
+    }
+}
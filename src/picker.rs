@@ -0,0 +1,211 @@
+//! A minimal terminal picker, used to choose between packages when a name doesn't resolve to exactly one.
+
+use clap::{error::ErrorKind, Error};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use std::io::{self, IsTerminal, Write};
+
+/// The most rows shown at once, beyond which the list scrolls.
+const MAX_ROWS: usize = 10;
+
+/// Whether there's a user at a terminal to pick from a list.
+/// Stdout is checked as well as stdin and stderr, so piped and CI invocations keep failing loudly.
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Lets the user choose one of `items`, returning its index,
+/// or `None` if they cancelled with escape or ctrl-c.
+pub fn pick(prompt: &str, items: &[String]) -> Result<Option<usize>, Error> {
+    let _raw = RawMode::enable().map_err(terminal_error)?;
+    let mut picker = Picker {
+        items,
+        filter: String::new(),
+        matches: (0..items.len()).collect(),
+        selected: 0,
+        offset: 0,
+    };
+
+    let mut stderr = io::stderr();
+    let result = picker.run(prompt, &mut stderr);
+    picker.clear(&mut stderr).map_err(terminal_error)?;
+    result.map_err(terminal_error)
+}
+
+struct Picker<'a> {
+    items: &'a [String],
+    filter: String,
+    /// Indices into `items` that match the current filter.
+    matches: Vec<usize>,
+    /// Index into `matches` of the highlighted row.
+    selected: usize,
+    /// Index into `matches` of the first visible row.
+    offset: usize,
+}
+
+impl Picker<'_> {
+    fn run(&mut self, prompt: &str, out: &mut impl Write) -> io::Result<Option<usize>> {
+        loop {
+            self.render(prompt, out)?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+
+            match key {
+                KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                    ..
+                }
+                | KeyEvent {
+                    code: KeyCode::Esc, ..
+                } => return Ok(None),
+                KeyEvent {
+                    code: KeyCode::Enter,
+                    ..
+                } => {
+                    if let Some(&index) = self.matches.get(self.selected) {
+                        return Ok(Some(index));
+                    }
+                }
+                KeyEvent {
+                    code: KeyCode::Up, ..
+                } => self.select(self.selected.saturating_sub(1)),
+                KeyEvent {
+                    code: KeyCode::Down,
+                    ..
+                } => self.select(self.selected + 1),
+                KeyEvent {
+                    code: KeyCode::Backspace,
+                    ..
+                } => {
+                    self.filter.pop();
+                    self.refilter();
+                }
+                KeyEvent {
+                    code: KeyCode::Char(c),
+                    modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                    ..
+                } => {
+                    self.filter.push(c);
+                    self.refilter();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Keeps items containing every whitespace-separated word of the filter, ignoring case.
+    fn refilter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        let words: Vec<&str> = filter.split_whitespace().collect();
+
+        self.matches = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                let item = item.to_lowercase();
+                words.iter().all(|word| item.contains(word))
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected.min(self.matches.len().saturating_sub(1));
+
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + MAX_ROWS {
+            self.offset = self.selected + 1 - MAX_ROWS;
+        }
+    }
+
+    fn render(&self, prompt: &str, out: &mut impl Write) -> io::Result<()> {
+        self.clear(out)?;
+
+        let width = match terminal::size() {
+            Ok((width, _)) if width > 0 => width as usize,
+            _ => 80,
+        };
+        queue!(out, Print(format!("{} {}", prompt, self.filter)))?;
+
+        let rows = self.matches.iter().enumerate().skip(self.offset).take(MAX_ROWS);
+        let mut drawn = 0;
+        for (position, &index) in rows {
+            let line: String = self.items[index]
+                .chars()
+                .take(width.saturating_sub(2))
+                .collect();
+            queue!(out, Print("\r\n"))?;
+
+            if position == self.selected {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(format!("> {}", line)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(format!("  {}", line)))?;
+            }
+            drawn += 1;
+        }
+
+        if self.matches.is_empty() {
+            queue!(out, Print("\r\n  (no matches)"))?;
+            drawn += 1;
+        }
+
+        // Leave the cursor at the end of the filter text
+        let column = (prompt.chars().count() + 1 + self.filter.chars().count()) as u16;
+        if drawn > 0 {
+            queue!(out, cursor::MoveUp(drawn))?;
+        }
+        queue!(out, cursor::MoveToColumn(column))?;
+
+        out.flush()
+    }
+
+    fn clear(&self, out: &mut impl Write) -> io::Result<()> {
+        queue!(
+            out,
+            cursor::MoveToColumn(0),
+            terminal::Clear(ClearType::FromCursorDown)
+        )?;
+
+        out.flush()
+    }
+}
+
+/// Puts the terminal into raw mode until dropped.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode().map(|_| RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn terminal_error(err: io::Error) -> Error {
+    Error::raw(ErrorKind::Io, format!("Terminal error: {}", err))
+}
@@ -0,0 +1,162 @@
+//! Opening crates so the editor can't save over them.
+//!
+//! When the editor is waited on, the crate's files are made read-only in place until it exits.
+//! Otherwise there's no telling when to give the permissions back, so a read-only copy of the
+//! crate is opened instead. Only installed crates are protected, as local packages are meant to
+//! be edited.
+
+use crate::{
+    editor::{self, DeferredSignals, Target},
+    files::{self, CopyOptions},
+    hash::StableHasher,
+    lockfile::cargo_home,
+    snapshot,
+};
+use clap::{error::ErrorKind, Error};
+use std::{
+    fs::{self, Permissions},
+    path::{Path, PathBuf},
+};
+
+
+/// The original permissions of everything made read-only, given back when dropped.
+pub struct Protection {
+    entries: Vec<(PathBuf, Permissions)>,
+    /// Dropped after the permissions are given back, so a signal can't end the run before then.
+    _signals: DeferredSignals,
+}
+
+impl Drop for Protection {
+    fn drop(&mut self) {
+        for (path, permissions) in self.entries.drain(..).rev() {
+            let _ = fs::set_permissions(&path, permissions);
+        }
+    }
+}
+
+/// Removes write permission from every file and directory of each target's crate
+/// until the returned guard is dropped.
+pub fn protect(targets: &[Target]) -> Result<Protection, Error> {
+    let mut protection = Protection {
+        entries: Vec::new(),
+        _signals: editor::defer_signals()?,
+    };
+
+    let mut roots = targets
+        .iter()
+        .map(|target| installed_root(&target.path))
+        .collect::<Result<Vec<_>, Error>>()?;
+    roots.sort();
+    roots.dedup();
+
+    for root in roots {
+        for path in files::walk(&root) {
+            let Ok(meta) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            if meta.file_type().is_symlink() || meta.permissions().readonly() {
+                continue;
+            }
+
+            let mut permissions = meta.permissions();
+            permissions.set_readonly(true);
+            fs::set_permissions(&path, permissions).map_err(|e| {
+                Error::raw(
+                    ErrorKind::Io,
+                    format!("Cannot make {} read-only: {}", path.display(), e),
+                )
+            })?;
+            protection.entries.push((path, meta.permissions()));
+        }
+    }
+
+    Ok(protection)
+}
+
+/// Copies each target's crate into `$CARGO_HOME/cargo-open/read-only` with its files made
+/// read-only, returning targets pointing into the copies. A copy is reused until the crate's
+/// contents change, as they do when it's edited or restored.
+pub fn copy_targets(targets: &[Target]) -> Result<Vec<Target>, Error> {
+    let cargo_home =
+        cargo_home().ok_or_else(|| Error::raw(ErrorKind::Io, "Cannot find cargo home directory"))?;
+    let copies = cargo_home.join("cargo-open").join("read-only");
+
+    targets
+        .iter()
+        .map(|target| {
+            let root = installed_root(&target.path)?;
+            let copy = copy_path(&copies, &cargo_home, &root).ok_or_else(|| {
+                Error::raw(
+                    ErrorKind::Io,
+                    format!("Cannot find where to copy {}", root.display()),
+                )
+            })?;
+
+            // Kept beside the copy, holding the fingerprint of the crate it was copied from
+            let name = copy.file_name().unwrap_or_default().to_string_lossy();
+            let fingerprint_path = copy.with_file_name(format!(".{}.fingerprint", name));
+            let fingerprint = format!("{:016x}", fingerprint(&root));
+            let is_current =
+                fs::read_to_string(&fingerprint_path).is_ok_and(|old| old == fingerprint);
+            if !is_current || !copy.exists() {
+                let options = CopyOptions {
+                    skip: &[],
+                    read_only: true,
+                };
+                files::copy_dir(&root, &copy, &options)
+                    .and_then(|_| fs::write(&fingerprint_path, &fingerprint))
+                    .map_err(|e| {
+                        Error::raw(
+                            ErrorKind::Io,
+                            format!("Cannot copy {}: {}", root.display(), e),
+                        )
+                    })?;
+            }
+
+            let path = match target.path.strip_prefix(&root) {
+                Ok(relative) if relative.as_os_str().is_empty() => copy,
+                Ok(relative) => copy.join(relative),
+                Err(_) => copy,
+            };
+            Ok(Target {
+                path,
+                position: target.position,
+            })
+        })
+        .collect()
+}
+
+/// The installed crate `path` is in. Anything else is refused, as it's meant to be edited.
+fn installed_root(path: &Path) -> Result<PathBuf, Error> {
+    snapshot::installed_root(path).ok_or_else(|| {
+        Error::raw(
+            ErrorKind::InvalidValue,
+            format!(
+                "Cannot open {} read-only, as it isn't an installed crate",
+                path.display()
+            ),
+        )
+    })
+}
+
+/// Where the copy of the crate at `root` goes, laid out as installed: at the same path relative to
+/// the cargo home, or at `rust-src/<toolchain>/<crate>` for the standard library's crates.
+fn copy_path(copies: &Path, cargo_home: &Path, root: &Path) -> Option<PathBuf> {
+    if let Ok(relative) = root.strip_prefix(cargo_home) {
+        return Some(copies.join(relative));
+    }
+
+    // `<toolchain>/lib/rustlib/src/rust/library/<crate>`
+    let toolchain = root.ancestors().nth(6)?.file_name()?;
+    Some(copies.join("rust-src").join(toolchain).join(root.file_name()?))
+}
+
+/// Identifies the contents of a crate, to tell whether a copy of it is still current.
+fn fingerprint(dir: &Path) -> u64 {
+    let mut hasher = StableHasher::default();
+    for (path, hash) in snapshot::hash_dir(dir) {
+        hasher.write_field(path.as_os_str().as_encoded_bytes());
+        hasher.write(&hash.to_le_bytes());
+    }
+    hasher.finish()
+}
@@ -0,0 +1,235 @@
+//! Opening crates straight from the `.crate` archives in cargo's local registry cache,
+//! for crates that aren't part of the current project.
+
+use crate::{
+    files, hash,
+    lockfile::{cargo_home, is_index_dir},
+    spec::{normalize_name, PackageSpec},
+};
+use cargo_metadata::semver::Version;
+use clap::{error::ErrorKind, Error};
+use flate2::read::GzDecoder;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// A `.crate` archive downloaded by cargo, found at `registry/cache/<index>/<name>-<version>.crate`.
+pub struct CachedCrate {
+    pub name: String,
+    pub version: Version,
+    /// The directory name cargo uses for the registry, e.g. `index.crates.io-1949cf8c6b5b557f`.
+    pub index: String,
+    pub archive: PathBuf,
+}
+
+impl CachedCrate {
+    /// The `<name>-<version>` directory name the archive unpacks to.
+    pub fn dir_name(&self) -> String {
+        format!("{}-{}", self.name, self.version)
+    }
+
+    /// Where cargo extracts this crate when it's built.
+    pub fn registry_src_dir(&self) -> Option<PathBuf> {
+        let src = cargo_home()?.join("registry").join("src");
+        Some(src.join(&self.index).join(self.dir_name()))
+    }
+}
+
+/// Finds the newest cached crate matching `spec`, across every registry in the cache unless it
+/// names one. Crates named exactly as given are preferred to ones differing in case or separators.
+pub fn find_crate(spec: &PackageSpec) -> Result<CachedCrate, Error> {
+    if spec.is_glob() {
+        return Err(Error::raw(
+            ErrorKind::InvalidValue,
+            format!("Cannot open {} from the local registry cache, name a single crate", spec.name),
+        ));
+    }
+
+    let cache = cargo_home()
+        .map(|cargo_home| cargo_home.join("registry").join("cache"))
+        .ok_or_else(|| Error::raw(ErrorKind::Io, "Cannot find cargo home directory"))?;
+
+    let mut candidates: Vec<CachedCrate> = cached_crates(&cache)
+        .into_iter()
+        .filter(|krate| {
+            normalize_name(&krate.name) == normalize_name(&spec.name)
+                && spec.matches_version(&krate.version)
+                && spec.url().is_none_or(|url| is_index_dir(url, &krate.index))
+        })
+        .collect();
+    if candidates.iter().any(|krate| krate.name == spec.name) {
+        candidates.retain(|krate| krate.name == spec.name);
+    }
+
+    candidates
+        .into_iter()
+        .max_by(|a, b| a.version.cmp(&b.version))
+        .ok_or_else(|| {
+            Error::raw(
+                ErrorKind::InvalidValue,
+                format!(
+                    "Package not found in the local registry cache ({}): {}",
+                    cache.display(),
+                    spec.name
+                ),
+            )
+        })
+}
+
+/// Lists the `.crate` archives in each registry's cache directory.
+fn cached_crates(cache: &Path) -> Vec<CachedCrate> {
+    let Ok(indexes) = fs::read_dir(cache) else {
+        return Vec::new();
+    };
+
+    let mut crates = Vec::new();
+    for index in indexes.filter_map(|entry| entry.ok()) {
+        let Ok(archives) = fs::read_dir(index.path()) else {
+            continue;
+        };
+
+        for archive in archives.filter_map(|entry| entry.ok()) {
+            let file_name = archive.file_name().to_string_lossy().into_owned();
+            let Some((name, version)) = parse_archive_name(&file_name) else {
+                continue;
+            };
+
+            crates.push(CachedCrate {
+                name: name.to_string(),
+                version,
+                index: index.file_name().to_string_lossy().into_owned(),
+                archive: archive.path(),
+            });
+        }
+    }
+
+    crates
+}
+
+/// Splits `<name>-<version>.crate` into its parts.
+fn parse_archive_name(file_name: &str) -> Option<(&str, Version)> {
+    parse_dir_name(file_name.strip_suffix(".crate")?)
+}
+
+/// Splits `<name>-<version>` into its parts. Both names and versions may contain `-`,
+/// so the split is made at the first `-` that's followed by a valid version.
+pub fn parse_dir_name(dir_name: &str) -> Option<(&str, Version)> {
+    dir_name.match_indices('-').find_map(|(index, _)| {
+        let version = Version::parse(&dir_name[index + 1..]).ok()?;
+        Some((&dir_name[..index], version))
+    })
+}
+
+/// Returns a directory holding the crate's source: cargo's own extraction if the crate has
+/// been built before, otherwise a copy unpacked into `$CARGO_HOME/cargo-open/src`.
+pub fn extract(krate: &CachedCrate) -> Result<PathBuf, Error> {
+    if let Some(dir) = krate.registry_src_dir() {
+        if dir.join(".cargo-ok").exists() {
+            return Ok(dir);
+        }
+    }
+
+    let scratch = cargo_home()
+        .map(|cargo_home| cargo_home.join("cargo-open").join("src").join(&krate.index))
+        .ok_or_else(|| Error::raw(ErrorKind::Io, "Cannot find cargo home directory"))?;
+    let dir = scratch.join(krate.dir_name());
+    if dir.join(".cargo-ok").exists() {
+        return Ok(dir);
+    }
+
+    files::stage_dir(&dir, |staging| {
+        unpack(&krate.archive, staging)?;
+        fs::write(staging.join(krate.dir_name()).join(".cargo-ok"), "ok")
+    })
+    .map_err(|e| {
+        Error::raw(
+            ErrorKind::Io,
+            format!("Cannot unpack {}: {}", krate.archive.display(), e),
+        )
+    })?;
+
+    Ok(dir)
+}
+
+/// Unpacks a `.crate` archive, a gzipped tarball with a single `<name>-<version>` directory, into `dest`.
+pub fn unpack(archive: &Path, dest: &Path) -> std::io::Result<()> {
+    let file = fs::File::open(archive)?;
+    fs::create_dir_all(dest)?;
+    tar::Archive::new(GzDecoder::new(file)).unpack(dest)
+}
+
+/// Finds the `.crate` archive a registry crate's directory was extracted from, whether by cargo
+/// into `registry/src/<index>/<name>-<version>` or by cargo-open into `cargo-open/src/<index>/<name>-<version>`.
+pub fn archive_for(dir: &Path) -> Option<PathBuf> {
+    let cargo_home = cargo_home()?;
+    let index = dir.parent()?;
+    let extract_roots = [
+        cargo_home.join("registry").join("src"),
+        cargo_home.join("cargo-open").join("src"),
+    ];
+    if !extract_roots.iter().any(|root| index.parent() == Some(root.as_path())) {
+        return None;
+    }
+
+    let archive = format!("{}.crate", dir.file_name()?.to_string_lossy());
+    let cache = cargo_home.join("registry").join("cache");
+    Some(cache.join(index.file_name()?).join(archive))
+}
+
+/// Hashes every file in a `.crate` archive, keyed by its path within the crate's directory.
+pub fn hash_archive(archive: &Path) -> std::io::Result<BTreeMap<PathBuf, u64>> {
+    let file = fs::File::open(archive)?;
+    let mut hashes = BTreeMap::new();
+
+    for entry in tar::Archive::new(GzDecoder::new(file)).entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        // Skip the `<name>-<version>` directory every entry is within
+        let path: PathBuf = entry.path()?.components().skip(1).collect();
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        hashes.insert(path, hash::hash_bytes(&contents));
+    }
+
+    Ok(hashes)
+}
+
+/// The library root of an unpacked crate, from its manifest's `[lib] path` or the default `src/lib.rs`.
+pub fn lib_path(dir: &Path) -> PathBuf {
+    let lib = fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| manifest.parse::<toml::Table>().ok())
+        .and_then(|manifest| {
+            let path = manifest.get("lib")?.get("path")?.as_str()?;
+            Some(PathBuf::from(path))
+        });
+
+    dir.join(lib.unwrap_or_else(|| PathBuf::from("src/lib.rs")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dir_names() {
+        let (name, version) = parse_dir_name("serde-1.0.201").unwrap();
+        assert_eq!((name, version.to_string().as_str()), ("serde", "1.0.201"));
+
+        // Hyphens in both the name and a pre-release version
+        let (name, version) = parse_dir_name("wasm-bindgen-0.2.0-alpha-1").unwrap();
+        assert_eq!(
+            (name, version.to_string().as_str()),
+            ("wasm-bindgen", "0.2.0-alpha-1")
+        );
+
+        assert!(parse_dir_name("serde").is_none());
+        assert!(parse_dir_name("serde-json").is_none());
+    }
+}
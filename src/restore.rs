@@ -0,0 +1,183 @@
+//! Putting modified dependencies back the way cargo left them.
+
+use crate::{files, lockfile::cargo_home, output, registry, verify};
+use clap::{error::ErrorKind, Error};
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Written by cargo once a crate or checkout is complete, so must survive a restore.
+const CARGO_OK: &str = ".cargo-ok";
+
+/// Restores each registry crate or git checkout containing `paths`,
+/// or with `dry_run` only reports what would change.
+pub fn restore_paths(paths: &[&Path], dry_run: bool) -> Result<(), Error> {
+    let mut report = String::new();
+
+    for dir in paths {
+        // Item paths open a file within the crate, so look for the crate's directory above it
+        let archive = dir
+            .ancestors()
+            .find_map(|dir| Some((dir, registry::archive_for(dir)?)));
+        let (root, changes) = if let Some((dir, archive)) = archive {
+            if !archive.is_file() {
+                return Err(Error::raw(
+                    ErrorKind::InvalidValue,
+                    format!(
+                        "Cannot restore {}, as {} is no longer in the cache",
+                        dir.display(),
+                        archive.display()
+                    ),
+                ));
+            }
+
+            let changes = verify::diff_archive(dir, &archive)?;
+            if !dry_run && !changes.is_empty() {
+                restore_archive(dir, &archive)?;
+            }
+            (dir.to_path_buf(), changes)
+        } else if let Some(root) = checkout_root(dir) {
+            let changes = git_changes(&root)?;
+            if !dry_run && !changes.is_empty() {
+                reset_checkout(&root)?;
+            }
+            (root, changes)
+        } else {
+            return Err(Error::raw(
+                ErrorKind::InvalidValue,
+                format!(
+                    "Cannot restore {}, as it isn't a registry crate or git checkout",
+                    dir.display()
+                ),
+            ));
+        };
+
+        if changes.is_empty() {
+            let _ = writeln!(report, "{} is unmodified", root.display());
+            continue;
+        }
+
+        let action = if dry_run { "Would restore" } else { "Restored" };
+        let _ = writeln!(report, "{} {}:", action, root.display());
+        for (change, path) in changes {
+            let _ = writeln!(report, "  {:8}  {}", change, path.display());
+        }
+    }
+
+    output::write_stdout(&[report.as_bytes()])
+}
+
+/// Replaces `dir` with a fresh extraction of the archive, staged with [`files::stage_dir`].
+/// Cargo's `.cargo-ok` marker is carried over, as the archive doesn't contain it.
+fn restore_archive(dir: &Path, archive: &Path) -> Result<(), Error> {
+    let dir_name = dir
+        .file_name()
+        .ok_or_else(|| Error::raw(ErrorKind::Io, "Path error"))?;
+
+    files::stage_dir(dir, |staging| {
+        registry::unpack(archive, staging)?;
+        match fs::copy(dir.join(CARGO_OK), staging.join(dir_name).join(CARGO_OK)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    })
+    .map_err(|e| {
+        Error::raw(
+            ErrorKind::Io,
+            format!("Cannot restore {}: {}", dir.display(), e),
+        )
+    })
+}
+
+/// The checkout `dir` is within, at `$CARGO_HOME/git/checkouts/<repo>/<commit>`.
+fn checkout_root(dir: &Path) -> Option<PathBuf> {
+    let checkouts = cargo_home()?.join("git").join("checkouts");
+    dir.ancestors()
+        .find(|dir| dir.parent().and_then(Path::parent) == Some(checkouts.as_path()))
+        .map(Path::to_path_buf)
+}
+
+/// Lists the files changed in a checkout, from `git status`.
+fn git_changes(root: &Path) -> Result<Vec<(&'static str, PathBuf)>, Error> {
+    let status = git(
+        root,
+        &["status", "--porcelain", "-z", "--untracked-files=all", "--no-renames"],
+    )?;
+
+    Ok(parse_status(&status))
+}
+
+/// Parses `git status --porcelain -z` output into changes sorted by path,
+/// leaving out the marker cargo adds to checkouts.
+fn parse_status(status: &str) -> Vec<(&'static str, PathBuf)> {
+    let mut changes: Vec<(&'static str, PathBuf)> = status
+        .split('\0')
+        .filter_map(|entry| {
+            let (code, path) = (entry.get(..2)?, entry.get(3..)?);
+            let change = match code {
+                "??" => "added",
+                _ if code.contains('D') => "removed",
+                _ => "modified",
+            };
+            Some((change, PathBuf::from(path)))
+        })
+        .filter(|(_, path)| path != Path::new(CARGO_OK))
+        .collect();
+
+    changes.sort_by(|a, b| a.1.cmp(&b.1));
+    changes
+}
+
+/// Discards every change to a checkout, keeping the marker cargo left in it.
+fn reset_checkout(root: &Path) -> Result<(), Error> {
+    git(root, &["reset", "--hard", "--quiet"])?;
+    git(root, &["clean", "-d", "--force", "--quiet", "--exclude", CARGO_OK])?;
+    Ok(())
+}
+
+fn git(root: &Path, args: &[&str]) -> Result<String, Error> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .map_err(|e| Error::raw(ErrorKind::Io, format!("Cannot run git: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(Error::raw(
+            ErrorKind::Io,
+            format!(
+                "git {} failed in {}: {}",
+                args.join(" "),
+                root.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_git_status() {
+        let status = " M src/lib.rs\0?? new file.rs\0 D README.md\0D  build.rs\0?? .cargo-ok\0";
+        let changes = parse_status(status);
+        assert_eq!(
+            changes,
+            [
+                ("removed", PathBuf::from("README.md")),
+                ("removed", PathBuf::from("build.rs")),
+                ("added", PathBuf::from("new file.rs")),
+                ("modified", PathBuf::from("src/lib.rs")),
+            ]
+        );
+        assert!(parse_status("").is_empty());
+    }
+}
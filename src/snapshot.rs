@@ -0,0 +1,190 @@
+//! Noticing edits made to installed crates while the editor was open.
+//!
+//! Registry and git sources are shared by every project on the machine, and cargo never checks
+//! them again once extracted, so an accidental save silently changes what gets built everywhere.
+
+use crate::{editor::Target, hash, lockfile::cargo_home, registry};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The hash of every file under an installed crate's directory.
+pub struct Snapshot {
+    dir: PathBuf,
+    kind: InstallKind,
+    hashes: BTreeMap<PathBuf, u64>,
+}
+
+/// Where an installed crate came from, which decides how to undo changes to it.
+#[derive(Clone, Copy)]
+enum InstallKind {
+    /// Extracted by cargo from a registry's `.crate` archive.
+    Registry,
+    /// Extracted by cargo-open from a registry's `.crate` archive, for `--registry`.
+    Extracted,
+    /// A git checkout made by cargo.
+    Git,
+    /// The standard library sources in the `rust-src` component.
+    RustSrc,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of each installed crate the targets are within.
+    /// Workspace members and path dependencies are left alone, as they're meant to be edited.
+    pub fn take_all(targets: &[Target]) -> Vec<Snapshot> {
+        let mut snapshots: Vec<Snapshot> = Vec::new();
+
+        for target in targets {
+            let Some((dir, kind)) = installed_dir(&target.path) else {
+                continue;
+            };
+            if snapshots.iter().any(|snapshot| snapshot.dir == dir) {
+                continue;
+            }
+
+            let hashes = hash_dir(&dir);
+            snapshots.push(Snapshot { dir, kind, hashes });
+        }
+
+        snapshots
+    }
+
+    /// Warns about any files added, removed or modified since the snapshot was taken,
+    /// and how to get the original sources back.
+    pub fn warn_changes(&self) {
+        let changes = diff(&self.hashes, &hash_dir(&self.dir));
+        if changes.is_empty() {
+            return;
+        }
+
+        eprintln!(
+            "warning: files in {} were changed while the editor was open:",
+            self.dir.display()
+        );
+        for (change, path) in &changes {
+            eprintln!("  {:8}  {}", change, path.display());
+        }
+
+        match self.kind {
+            InstallKind::Extracted => {
+                eprintln!("These sources are cargo-open's own copy, used by `cargo open --registry`.")
+            }
+            _ => eprintln!("These sources are shared by every project that depends on this crate."),
+        }
+        match self.kind {
+            InstallKind::Registry | InstallKind::Extracted => {
+                let dir_name = self.dir.file_name().unwrap_or_default().to_string_lossy();
+                // cargo-open's own copies aren't in any project, so can only be found in the registry
+                let registry = match self.kind {
+                    InstallKind::Extracted => " --registry",
+                    _ => "",
+                };
+                match registry::parse_dir_name(&dir_name) {
+                    Some((name, version)) => eprintln!(
+                        "To undo the changes, restore it from its archive:\n  cargo open{} --restore {}@{}",
+                        registry, name, version
+                    ),
+                    None => eprintln!(
+                        "To undo the changes, remove the directory and it will be extracted again:\n  rm -r {}",
+                        shell_words::quote(&self.dir.to_string_lossy())
+                    ),
+                }
+            }
+            InstallKind::Git => eprintln!(
+                "To undo the changes, reset the checkout with `cargo open --restore`, or:\n  cd {} && git reset --hard && git clean -fd",
+                shell_words::quote(&self.dir.to_string_lossy())
+            ),
+            InstallKind::RustSrc => eprintln!(
+                "To undo the changes, reinstall the component:\n  rustup component remove rust-src && rustup component add rust-src"
+            ),
+        }
+    }
+}
+
+/// The directory of the installed crate `path` is in, if it's in one.
+pub fn installed_root(path: &Path) -> Option<PathBuf> {
+    installed_dir(path).map(|(dir, _)| dir)
+}
+
+/// Finds the installed crate directory `path` is in: `$CARGO_HOME/registry/src/<index>/<crate>`,
+/// `$CARGO_HOME/git/checkouts/<repo>/<commit>`, cargo-open's own extractions in
+/// `$CARGO_HOME/cargo-open/src/<index>/<crate>`, or `rustlib/src/rust/library/<crate>` in a sysroot.
+fn installed_dir(path: &Path) -> Option<(PathBuf, InstallKind)> {
+    if let Some(cargo_home) = cargo_home() {
+        let roots = [
+            (cargo_home.join("registry").join("src"), InstallKind::Registry),
+            (cargo_home.join("cargo-open").join("src"), InstallKind::Extracted),
+            (cargo_home.join("git").join("checkouts"), InstallKind::Git),
+        ];
+
+        for (root, kind) in roots {
+            let dir = path
+                .ancestors()
+                .find(|dir| dir.parent().and_then(Path::parent) == Some(root.as_path()));
+            if let Some(dir) = dir {
+                return Some((dir.to_path_buf(), kind));
+            }
+        }
+    }
+
+    let dir = path.ancestors().find(|dir| {
+        dir.parent()
+            .is_some_and(|parent| parent.ends_with("rustlib/src/rust/library"))
+    })?;
+    Some((dir.to_path_buf(), InstallKind::RustSrc))
+}
+
+/// The files added, removed or modified between two sets of hashes, sorted by path.
+pub fn diff(
+    old: &BTreeMap<PathBuf, u64>,
+    new: &BTreeMap<PathBuf, u64>,
+) -> Vec<(&'static str, PathBuf)> {
+    let mut changes = Vec::new();
+    for (path, hash) in old {
+        match new.get(path) {
+            None => changes.push(("removed", path.clone())),
+            Some(new_hash) if new_hash != hash => changes.push(("modified", path.clone())),
+            Some(_) => {}
+        }
+    }
+    for path in new.keys() {
+        if !old.contains_key(path) {
+            changes.push(("added", path.clone()));
+        }
+    }
+
+    changes.sort_by(|a, b| a.1.cmp(&b.1));
+    changes
+}
+
+/// Hashes every file under `dir`, keyed by its path relative to `dir`.
+pub fn hash_dir(dir: &Path) -> BTreeMap<PathBuf, u64> {
+    let mut hashes = BTreeMap::new();
+    let mut stack = vec![dir.to_path_buf()];
+
+    while let Some(current) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&current) else {
+            continue;
+        };
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+
+            if file_type.is_dir() {
+                if entry.file_name() != ".git" {
+                    stack.push(path);
+                }
+            } else if let Ok(contents) = fs::read(&path) {
+                let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+                hashes.insert(relative, hash::hash_bytes(&contents));
+            }
+        }
+    }
+
+    hashes
+}
@@ -0,0 +1,318 @@
+//! Parsing and matching of cargo package id specs, as accepted by `cargo pkgid` and `cargo -p`.
+
+use cargo_metadata::{
+    semver::{Version, VersionReq},
+    Package,
+};
+use clap::{error::ErrorKind, Error};
+
+/// A package id spec such as `syn`, `syn@1.0.109`, `syn@^1` or
+/// `registry+https://github.com/rust-lang/crates.io-index#syn@2.0.0`.
+pub struct PackageSpec {
+    pub name: String,
+    version: Option<VersionMatcher>,
+    url: Option<String>,
+}
+
+enum VersionMatcher {
+    /// A full version, which must match exactly.
+    Exact(Version),
+    /// A version with trailing components missing, like `1` or `1.0`, matching any version with that prefix.
+    Partial(Vec<u64>),
+    /// A version requirement, like `^1` or `>=2, <3`.
+    Req(VersionReq),
+}
+
+impl PackageSpec {
+    pub fn parse(spec: &str) -> Result<Self, Error> {
+        let (url, fragment) = match spec.rsplit_once('#') {
+            Some((url, fragment)) => (Some(url), fragment),
+            // A bare url, which would otherwise be taken for `<name>:<version>`
+            None if spec.contains("://") => (Some(spec), ""),
+            None => (None, spec),
+        };
+
+        let (name, version) = match fragment.split_once(['@', ':']) {
+            Some((name, version)) => (name, Some(version)),
+            None if url.is_some() && fragment.starts_with(|c: char| c.is_ascii_digit()) => {
+                ("", Some(fragment))
+            }
+            None => (fragment, None),
+        };
+
+        // `<url>` and `<url>#<version>` name the package after the last segment of the url
+        let name = match (name, url) {
+            ("", Some(url)) => strip_url(url).rsplit('/').next().unwrap_or_default(),
+            (name, _) => name,
+        };
+
+        if name.is_empty() {
+            return Err(invalid_spec(spec, "missing package name"));
+        }
+
+        let version = version
+            .map(|version| VersionMatcher::parse(version).map_err(|e| invalid_spec(spec, &e)))
+            .transpose()?;
+
+        Ok(PackageSpec {
+            name: name.to_string(),
+            version,
+            url: url.map(String::from),
+        })
+    }
+
+    /// Matches packages by name regardless of case and `-`/`_` separators, as well as by version and source.
+    /// Callers should prefer packages whose name matches exactly, see [`PackageSpec::matches_exactly`].
+    pub fn matches(&self, package: &Package) -> bool {
+        let name_matches = if self.is_glob() {
+            glob_matches(&fold_name(&self.name), &fold_name(&package.name))
+        } else {
+            normalize_name(&package.name) == normalize_name(&self.name)
+        };
+
+        name_matches && self.matches_version_and_source(package)
+    }
+
+    /// Whether the name is a pattern like `tokio-*`, which may match any number of packages.
+    pub fn is_glob(&self) -> bool {
+        self.name.contains(['*', '?'])
+    }
+
+    /// The source url, if the spec names one.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    pub fn matches_exactly(&self, package: &Package) -> bool {
+        package.name == self.name && self.matches(package)
+    }
+
+    /// Matches everything but the name, for packages found under another name such as a dependency rename.
+    pub fn matches_version_and_source(&self, package: &Package) -> bool {
+        let source = match &package.source {
+            Some(source) => source.repr.as_str(),
+            None => package.id.repr.split('#').next().unwrap_or_default(),
+        };
+
+        self.matches_version(&package.version) && self.matches_source(source)
+    }
+
+    /// Matches a `[[package]]` entry from `Cargo.lock`, which only counts if its name matches exactly.
+    pub fn matches_locked(&self, name: &str, version: &Version, source: &str) -> bool {
+        name == self.name && self.matches_version(version) && self.matches_source(source)
+    }
+
+    pub fn matches_version(&self, version: &Version) -> bool {
+        self.version
+            .as_ref()
+            .is_none_or(|matcher| matcher.matches(version))
+    }
+
+    fn matches_source(&self, source: &str) -> bool {
+        self.url
+            .as_ref()
+            .is_none_or(|url| strip_url(source) == strip_url(url))
+    }
+}
+
+/// Folds case and treats `_` as `-`, keeping separators so globs like `tokio-*` don't match `tokio` itself.
+fn fold_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('_', "-")
+}
+
+/// Matches `name` against a pattern where `*` stands for any run of characters and `?` for any one.
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*` if the rest of the pattern fails to match
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, start)) => {
+                    p = star + 1;
+                    n = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Folds case and drops separators, so `serde-json`, `serde_json` and `SerdeJson` compare equal.
+pub fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl VersionMatcher {
+    fn parse(version: &str) -> Result<Self, String> {
+        if let Ok(version) = Version::parse(version) {
+            return Ok(VersionMatcher::Exact(version));
+        }
+
+        let is_partial = version.split('.').count() < 3;
+        if is_partial && version.split('.').all(|part| part.parse::<u64>().is_ok()) {
+            let parts = version.split('.').map(|part| part.parse().unwrap());
+            return Ok(VersionMatcher::Partial(parts.collect()));
+        }
+
+        VersionReq::parse(version)
+            .map(VersionMatcher::Req)
+            .map_err(|e| e.to_string())
+    }
+
+    fn matches(&self, version: &Version) -> bool {
+        match self {
+            VersionMatcher::Exact(exact) => exact == version,
+            VersionMatcher::Partial(parts) => parts
+                .iter()
+                .zip([version.major, version.minor, version.patch])
+                .all(|(part, actual)| *part == actual),
+            VersionMatcher::Req(req) => req.matches(version),
+        }
+    }
+}
+
+/// Reduces a source url to the part that identifies it,
+/// dropping the source kind, any git reference and a trailing slash.
+fn strip_url(url: &str) -> &str {
+    let url = url.split_once('+').map_or(url, |(_, url)| url);
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    url.trim_end_matches('/')
+}
+
+fn invalid_spec(spec: &str, reason: &str) -> Error {
+    Error::raw(
+        ErrorKind::InvalidValue,
+        format!("Invalid package spec {:?}: {}", spec, reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRATES_IO: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn parses_name_and_version() {
+        let spec = PackageSpec::parse("syn").unwrap();
+        assert_eq!(spec.name, "syn");
+        assert!(spec.version.is_none() && spec.url.is_none());
+
+        for spec in ["syn@1.0.109", "syn:1.0.109"] {
+            let spec = PackageSpec::parse(spec).unwrap();
+            assert_eq!(spec.name, "syn");
+            assert!(matches!(spec.version, Some(VersionMatcher::Exact(_))));
+        }
+    }
+
+    #[test]
+    fn parses_url_specs() {
+        let spec = PackageSpec::parse(&format!("{}#syn@2.0.0", CRATES_IO)).unwrap();
+        assert_eq!(spec.name, "syn");
+        assert_eq!(spec.url.as_deref(), Some(CRATES_IO));
+
+        // Without a name, the package is named after the url
+        let spec = PackageSpec::parse("https://github.com/dtolnay/syn#2.0.0").unwrap();
+        assert_eq!(spec.name, "syn");
+        assert!(spec.matches_version(&version("2.0.0")));
+    }
+
+    #[test]
+    fn parses_bare_urls() {
+        let spec = PackageSpec::parse("https://github.com/serde-rs/serde").unwrap();
+        assert_eq!(spec.name, "serde");
+        assert!(spec.version.is_none());
+        let git = "git+https://github.com/serde-rs/serde?branch=master#0123456789abcdef";
+        assert!(spec.matches_locked("serde", &version("1.0.0"), git));
+        assert!(!spec.matches_locked("serde", &version("1.0.0"), CRATES_IO));
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        assert!(PackageSpec::parse("@1.0.0").is_err());
+        assert!(PackageSpec::parse("syn@not-a-version").is_err());
+    }
+
+    #[test]
+    fn matches_exact_versions() {
+        let spec = PackageSpec::parse("syn@1.0.109").unwrap();
+        assert!(spec.matches_version(&version("1.0.109")));
+        assert!(!spec.matches_version(&version("1.0.108")));
+    }
+
+    #[test]
+    fn matches_partial_versions_by_prefix() {
+        let spec = PackageSpec::parse("syn@1").unwrap();
+        assert!(matches!(spec.version, Some(VersionMatcher::Partial(_))));
+        assert!(spec.matches_version(&version("1.0.109")));
+        assert!(!spec.matches_version(&version("2.0.0")));
+
+        let spec = PackageSpec::parse("syn@0.15").unwrap();
+        assert!(spec.matches_version(&version("0.15.44")));
+        assert!(!spec.matches_version(&version("0.1.5")));
+    }
+
+    #[test]
+    fn matches_version_requirements() {
+        let spec = PackageSpec::parse("syn@>=1.0, <2").unwrap();
+        assert!(matches!(spec.version, Some(VersionMatcher::Req(_))));
+        assert!(spec.matches_version(&version("1.5.0")));
+        assert!(!spec.matches_version(&version("2.0.0")));
+    }
+
+    #[test]
+    fn matches_locked_packages_by_source() {
+        let spec = PackageSpec::parse(&format!("{}/#syn@2", CRATES_IO)).unwrap();
+        assert!(spec.matches_locked("syn", &version("2.0.1"), CRATES_IO));
+        let git = "git+https://github.com/dtolnay/syn";
+        assert!(!spec.matches_locked("syn", &version("2.0.1"), git));
+        assert!(!spec.matches_locked("Syn", &version("2.0.1"), CRATES_IO));
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_matches("tokio-*", "tokio-util"));
+        assert!(glob_matches("*-derive", "serde-derive"));
+        assert!(glob_matches("s?n", "syn"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("a*b*c", "aXbYbZc"));
+        assert!(!glob_matches("tokio-*", "tokio"));
+        assert!(!glob_matches("s?n", "sn"));
+        assert!(!glob_matches("a*b", "acbd"));
+    }
+
+    #[test]
+    fn folds_names_for_globs() {
+        let spec = PackageSpec::parse("Serde_*").unwrap();
+        assert!(spec.is_glob());
+        assert!(glob_matches(&fold_name(&spec.name), &fold_name("serde-json")));
+        assert!(!PackageSpec::parse("serde").unwrap().is_glob());
+    }
+
+    #[test]
+    fn normalizes_names() {
+        assert_eq!(normalize_name("Serde_JSON"), normalize_name("serde-json"));
+        assert_ne!(normalize_name("serde"), normalize_name("serde-json"));
+    }
+}
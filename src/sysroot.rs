@@ -0,0 +1,76 @@
+//! Finding the standard library's sources in the active toolchain's sysroot.
+
+use clap::{error::ErrorKind, Error};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The crates shipped with the toolchain, whose sources come from the `rust-src` component.
+pub const STD_CRATES: &[&str] = &["std", "core", "alloc", "proc_macro", "test"];
+
+/// Finds the source directory of a standard library crate. `rustc` is run from the manifest's
+/// directory, so rustup picks the same toolchain cargo would, honouring `rust-toolchain.toml`
+/// and `cargo +toolchain` overrides.
+pub fn find_std_crate(name: &str, manifest_path: Option<&Path>) -> Result<PathBuf, Error> {
+    let sysroot = sysroot(manifest_path)?;
+    let library = sysroot
+        .join("lib")
+        .join("rustlib")
+        .join("src")
+        .join("rust")
+        .join("library");
+
+    if !library.is_dir() {
+        return Err(Error::raw(
+            ErrorKind::Io,
+            format!(
+                "Standard library sources not found in {}\n\n  install them with: rustup component add rust-src",
+                sysroot.display()
+            ),
+        ));
+    }
+
+    let dir = library.join(name);
+    if !dir.is_dir() {
+        return Err(Error::raw(
+            ErrorKind::InvalidValue,
+            format!("Package not found in {}: {}", library.display(), name),
+        ));
+    }
+
+    Ok(dir)
+}
+
+fn sysroot(manifest_path: Option<&Path>) -> Result<PathBuf, Error> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let mut cmd = Command::new(&rustc);
+    cmd.args(["--print", "sysroot"]);
+
+    let dir = manifest_path
+        .and_then(Path::parent)
+        .filter(|dir| !dir.as_os_str().is_empty());
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+
+    let output = cmd.output().map_err(|e| {
+        Error::raw(
+            ErrorKind::Io,
+            format!("Cannot run {}: {}", rustc.to_string_lossy(), e),
+        )
+    })?;
+
+    if !output.status.success() {
+        return Err(Error::raw(
+            ErrorKind::Io,
+            format!(
+                "Cannot find sysroot: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+
+    let sysroot = String::from_utf8_lossy(&output.stdout);
+    Ok(PathBuf::from(sysroot.trim()))
+}
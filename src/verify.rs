@@ -0,0 +1,101 @@
+//! Checking the extracted sources of registry crates against the `.crate` archives they came from.
+
+use crate::{output, registry, snapshot, source_kind};
+use cargo_metadata::Package;
+use clap::{error::ErrorKind, Error};
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+/// Files written alongside a crate's sources when it's extracted, which aren't in the archive.
+const EXTRACTION_FILES: &[&str] = &[".cargo-ok"];
+
+/// Compares each package's directory with its archive, printing any files that were added,
+/// removed or modified. Returns whether every package matched.
+pub fn verify_packages(packages: &[&Package]) -> Result<bool, Error> {
+    let mut report = String::new();
+    let (mut modified, mut skipped) = (0, 0);
+
+    for package in packages {
+        let dir = package
+            .manifest_path
+            .parent()
+            .map(|dir| dir.as_std_path())
+            .ok_or_else(|| Error::raw(ErrorKind::Io, "Path error"))?;
+
+        let archive = registry::archive_for(dir).filter(|_| source_kind(package) == "registry");
+        let Some(archive) = archive else {
+            return Err(Error::raw(
+                ErrorKind::InvalidValue,
+                format!(
+                    "Package {} isn't extracted from a registry archive, so can't be verified",
+                    package.name
+                ),
+            ));
+        };
+        if !archive.is_file() {
+            eprintln!(
+                "warning: skipping {} {}, as {} is no longer in the cache",
+                package.name,
+                package.version,
+                archive.display()
+            );
+            skipped += 1;
+            continue;
+        }
+
+        let changes = diff_archive(dir, &archive)?;
+        if changes.is_empty() {
+            continue;
+        }
+
+        modified += 1;
+        let _ = writeln!(
+            report,
+            "{} {}: {} files differ from {}",
+            package.name,
+            package.version,
+            changes.len(),
+            archive.display()
+        );
+        for (change, path) in changes {
+            let _ = writeln!(report, "  {:8}  {}", change, path.display());
+        }
+    }
+
+    output::write_stdout(&[report.as_bytes()])?;
+    let verified = packages.len() - skipped;
+    eprintln!(
+        "Verified {} crate{}: {} modified, {} skipped",
+        verified,
+        if verified == 1 { "" } else { "s" },
+        modified,
+        skipped
+    );
+
+    Ok(modified == 0)
+}
+
+/// Whether a package can be checked against an archive, for verifying every package in the graph.
+pub fn is_verifiable(package: &Package) -> bool {
+    let dir = package.manifest_path.parent().map(|dir| dir.as_std_path());
+    source_kind(package) == "registry" && dir.and_then(registry::archive_for).is_some()
+}
+
+/// The files added, removed or modified in `dir` since it was extracted from `archive`.
+pub fn diff_archive(dir: &Path, archive: &Path) -> Result<Vec<(&'static str, PathBuf)>, Error> {
+    let original = registry::hash_archive(archive).map_err(|e| {
+        Error::raw(
+            ErrorKind::Io,
+            format!("Cannot read {}: {}", archive.display(), e),
+        )
+    })?;
+
+    let mut current = snapshot::hash_dir(dir);
+    for file in EXTRACTION_FILES {
+        current.remove(Path::new(file));
+    }
+
+    Ok(snapshot::diff(&original, &current))
+}